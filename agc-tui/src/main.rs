@@ -1,5 +1,8 @@
-use agc::cpu::Cpu;
+use agc::cpu::{Cpu, EmulationError};
+use agc::debugger::{Breakpoint, Debugger, Expression, ExpressionError, Stop};
+use agc::history::{History, HistoryError};
 use agc::memory::load_yayul_img_file;
+use agc::word::{W12, W3, W5};
 use crossterm::cursor::*;
 use crossterm::event::*;
 use crossterm::style::*;
@@ -8,6 +11,9 @@ use crossterm::*;
 use std::io::{stdout, Stdout, Write};
 use std::path::PathBuf;
 
+/// Maximum number of control pulses run by a continue
+const RUN_MAX_PULSES: u64 = 1_000_000;
+
 fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     // Initialize terminal
     let mut stdout = stdout();
@@ -23,30 +29,62 @@ fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 
     // Initialize the emulator
     let mut cpu = init_emulator()?;
+    let mut history = History::new(&cpu);
 
     // Initialize the registers
     let mut registers = Registers::new();
+    let mut debugging = Debugging::new();
 
     // Run the emulator
-    redraw(&mut stdout, &cpu, &mut registers)?;
+    redraw(&mut stdout, &cpu, &mut registers, &debugging)?;
     loop {
-        match read()? {
-            Event::Key(event) => match event.code {
+        if let Event::Key(event) = read()? {
+            match event.code {
                 KeyCode::Right => {
-                    cpu.step_control_pulse();
+                    debugging.report_step(history.step_control_pulse(&mut cpu));
                 }
                 KeyCode::Down => {
-                    cpu.step_subinstruction();
+                    debugging.report_step(history.step_subinstruction(&mut cpu));
+                }
+                KeyCode::Left => {
+                    debugging.report_step_back(history.step_back_control_pulse(&mut cpu));
+                }
+                KeyCode::Up => {
+                    debugging.report_step_back(history.step_back_subinstruction(&mut cpu));
+                }
+                // Back to the previous instruction fetch
+                KeyCode::PageUp => {
+                    let result = history.run_back_until_changed(&mut cpu, |cpu| cpu.z);
+                    debugging.report_step_back(result);
+                }
+                KeyCode::Char('c') => {
+                    debugging.run(&mut cpu, &mut history);
+                }
+                KeyCode::Char('b') => {
+                    let prompt = "Break at [BANK,]ADDRESS [if CONDITION]: ";
+                    if let Some(line) = read_line(&mut stdout, prompt)? {
+                        debugging.add_breakpoint(&line);
+                    }
+                }
+                KeyCode::Char('w') => {
+                    let prompt = "Watch EXPRESSION [if CONDITION]: ";
+                    if let Some(line) = read_line(&mut stdout, prompt)? {
+                        debugging.add_watch(&line);
+                    }
+                }
+                KeyCode::Char('d') => {
+                    if let Some(line) = read_line(&mut stdout, "Delete #")? {
+                        debugging.remove(&line);
+                    }
                 }
                 KeyCode::Esc => {
                     break;
                 }
                 _ => (),
-            },
-            _ => (),
+            }
         }
 
-        redraw(&mut stdout, &cpu, &mut registers)?;
+        redraw(&mut stdout, &cpu, &mut registers, &debugging)?;
     }
 
     // Restore terminal
@@ -72,45 +110,251 @@ fn init_emulator() -> std::result::Result<Cpu, Box<dyn std::error::Error>> {
     filepath.push("listings");
     filepath.push("Aurora12.bin");
     let fixed_memory = load_yayul_img_file(filepath)?;
-    Ok(Cpu::new(fixed_memory))
+    let mut cpu = Cpu::new(fixed_memory);
+    cpu.set_restart_alarms(true);
+    Ok(cpu)
 }
 
-fn redraw(stdout: &mut Stdout, cpu: &Cpu, registers: &mut Registers) -> Result<()> {
+/// Read a line at the bottom of the terminal, returns `None` on Esc
+fn read_line(stdout: &mut Stdout, prompt: &str) -> Result<Option<String>> {
+    let (_, rows) = size()?;
+    let mut line = String::new();
+    loop {
+        stdout
+            .queue(MoveTo(0, rows.saturating_sub(1)))?
+            .queue(Clear(ClearType::CurrentLine))?
+            .queue(Print(format!("{}{}", prompt, line)))?;
+        stdout.flush()?;
+
+        if let Event::Key(event) = read()? {
+            match event.code {
+                KeyCode::Enter => return Ok(Some(line)),
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                KeyCode::Char(c) => line.push(c),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn redraw(
+    stdout: &mut Stdout,
+    cpu: &Cpu,
+    registers: &mut Registers,
+    debugging: &Debugging,
+) -> Result<()> {
+    let subinstruction = cpu.try_current_subinstruction();
+    let name = match subinstruction {
+        Ok(subinstruction) => subinstruction.name,
+        Err(_) => "HALTED",
+    };
     stdout
         .queue(Clear(ClearType::All))?
         .queue(MoveTo(0, 0))?
         .queue(PrintStyledContent(
-            format!(
-                "T{:02} - {}",
-                usize::from(cpu.current_timepulse),
-                cpu.current_subsintruction_name()
-            )
-            .reverse(),
+            format!("T{:02} - {}", usize::from(cpu.current_timepulse), name).reverse(),
         ))?
         .queue(MoveToNextLine(1))?;
 
-    // Print the next control pulses
-    let control_pulses = cpu
-        .current_subinstruction()
-        .actions(cpu.current_timepulse)
-        .iter()
-        .filter(|action| action.execute(cpu.br))
-        .map(|action| action.control_pulse().name)
-        .collect::<Vec<_>>()
-        .join(" ");
-    stdout.queue(Print(format!("Next pulses: [{}]", control_pulses)))?;
+    // Print the next control pulses, or why the emulation stopped
+    match subinstruction {
+        Ok(subinstruction) => {
+            let control_pulses = subinstruction
+                .actions(cpu.current_timepulse)
+                .iter()
+                .filter(|action| action.execute(cpu.br))
+                .map(|action| action.control_pulse().name)
+                .collect::<Vec<_>>()
+                .join(" ");
+            stdout.queue(Print(format!("Next pulses: [{}]", control_pulses)))?;
+        }
+        Err(error) => {
+            stdout.queue(Print(format!("Error: {}", error)))?;
+        }
+    }
     stdout.queue(MoveToNextLine(1))?;
 
     registers.print_public_registers(stdout, cpu)?;
     stdout.queue(MoveToNextLine(1))?;
     registers.print_private_registers(stdout, cpu)?;
     stdout.queue(MoveToNextLine(1))?;
+    debugging.print(stdout, cpu)?;
 
     stdout.flush()?;
 
     Ok(())
 }
 
+/// Breakpoints and watch expressions, with the text they were entered with
+struct Debugging {
+    debugger: Debugger,
+    breakpoints: Vec<(usize, String)>,
+    watches: Vec<(usize, String)>,
+    /// Result of the last command
+    status: String,
+}
+
+impl Debugging {
+    fn new() -> Self {
+        Self {
+            debugger: Debugger::new(),
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            status: String::new(),
+        }
+    }
+
+    fn run(&mut self, cpu: &mut Cpu, history: &mut History) {
+        self.status = match self
+            .debugger
+            .run_until_break_with_history(cpu, history, RUN_MAX_PULSES)
+        {
+            Ok(Stop::Breakpoint(id)) => format!("Stopped at breakpoint #{}", id),
+            Ok(Stop::Watchpoint(id)) => format!("Stopped by watch #{}", id),
+            Ok(Stop::MaxPulses) => format!("Stopped after {} control pulses", RUN_MAX_PULSES),
+            Err(error) => format!("Error: {}", error),
+        };
+    }
+
+    /// Show the error of a step, the CPU is left untouched
+    fn report_step(&mut self, result: std::result::Result<(), EmulationError>) {
+        self.status = match result {
+            Ok(()) => String::new(),
+            Err(error) => format!("Error: {}", error),
+        };
+    }
+
+    /// Show the error of a step back, or that no earlier position was found
+    fn report_step_back(&mut self, result: std::result::Result<bool, HistoryError>) {
+        self.status = match result {
+            Ok(true) => String::new(),
+            Ok(false) => "No earlier position in the history".to_string(),
+            Err(error) => format!("Error: {}", error),
+        };
+    }
+
+    fn add_breakpoint(&mut self, line: &str) {
+        let result = split_condition(line).and_then(|(address, condition)| {
+            // The bank is EBANK in switched erasable memory, FBANK otherwise
+            let breakpoint = match address.split_once(',') {
+                Some((bank, address)) => {
+                    let address = parse_octal(address, 0o7777)?;
+                    if address < 0o2000 {
+                        Breakpoint::Address {
+                            ebank: Some(W3::from(parse_octal(bank, 0o7)?)),
+                            fbank: None,
+                            address: W12::from(address),
+                        }
+                    } else {
+                        Breakpoint::Address {
+                            ebank: None,
+                            fbank: Some(W5::from(parse_octal(bank, 0o37)?)),
+                            address: W12::from(address),
+                        }
+                    }
+                }
+                None => Breakpoint::Address {
+                    ebank: None,
+                    fbank: None,
+                    address: W12::from(parse_octal(address, 0o7777)?),
+                },
+            };
+            let id = self
+                .debugger
+                .add_breakpoint(breakpoint)
+                .map_err(|error| error.to_string())?;
+            self.debugger.set_condition(id, condition);
+            Ok(id)
+        });
+        self.status = match result {
+            Ok(id) => {
+                self.breakpoints.push((id, line.trim().to_string()));
+                format!("Added breakpoint #{}", id)
+            }
+            Err(error) => format!("Error: {}", error),
+        };
+    }
+
+    fn add_watch(&mut self, line: &str) {
+        let result = split_condition(line).and_then(|(expression, condition)| {
+            let expression = expression
+                .parse::<Expression>()
+                .map_err(|error| error.to_string())?;
+            let id = self.debugger.add_watch_expression(expression);
+            self.debugger.set_condition(id, condition);
+            Ok(id)
+        });
+        self.status = match result {
+            Ok(id) => {
+                self.watches.push((id, line.trim().to_string()));
+                format!("Added watch #{}", id)
+            }
+            Err(error) => format!("Error: {}", error),
+        };
+    }
+
+    fn remove(&mut self, line: &str) {
+        let debugger = &mut self.debugger;
+        let id = line
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|id| debugger.remove(*id));
+        self.status = match id {
+            Some(id) => {
+                self.breakpoints.retain(|(i, _)| *i != id);
+                self.watches.retain(|(i, _)| *i != id);
+                format!("Deleted #{}", id)
+            }
+            None => format!("Error: #{} does not exist", line.trim()),
+        };
+    }
+
+    fn print(&self, stdout: &mut Stdout, cpu: &Cpu) -> Result<()> {
+        for (id, text) in &self.breakpoints {
+            let hits = self.debugger.hits(*id).unwrap_or(0);
+            stdout
+                .queue(Print(format!("#{} break {} (hits: {})", id, text, hits)))?
+                .queue(MoveToNextLine(1))?;
+        }
+        for (id, text) in &self.watches {
+            let hits = self.debugger.hits(*id).unwrap_or(0);
+            let value = self
+                .debugger
+                .watch_expression(*id)
+                .map_or(0, |expression| expression.evaluate(cpu, hits));
+            stdout
+                .queue(Print(format!("#{} watch {} = {:o}", id, text, value)))?
+                .queue(MoveToNextLine(1))?;
+        }
+        stdout.queue(Print(&self.status))?;
+        Ok(())
+    }
+}
+
+/// Split `TEXT if CONDITION`
+fn split_condition(line: &str) -> std::result::Result<(&str, Option<Expression>), String> {
+    match line.split_once(" if ") {
+        Some((text, condition)) => {
+            let condition = condition
+                .parse()
+                .map_err(|error: ExpressionError| error.to_string())?;
+            Ok((text, Some(condition)))
+        }
+        None => Ok((line, None)),
+    }
+}
+
+fn parse_octal(text: &str, max: u16) -> std::result::Result<u16, String> {
+    u16::from_str_radix(text.trim(), 8)
+        .ok()
+        .filter(|value| *value <= max)
+        .ok_or_else(|| format!("invalid octal number {}", text.trim()))
+}
+
 struct Registers {
     // Public registers
     a: PrintedRegister,
@@ -0,0 +1,177 @@
+use crate::word::W16;
+
+/// 16-bit one's complement adder
+///
+/// The adder is a ripple-carry chain of 16 full adders. The carry out of
+/// bit 16 is fed back into bit 1 (end-around carry), together with the
+/// carry flip-flop CI. Both drive the same carry input, so the end-around
+/// carry is never added twice. Bit 16 is the sign of the result and bit 15
+/// is the sign of the uncorrected result, which is how overflow is
+/// detected.
+///
+/// When `neac` is set, the end-around carry is inhibited and the adder
+/// works in two's complement, as needed by the CDU counters.
+pub fn add(x: W16, y: W16, ci: bool, neac: bool) -> W16 {
+    let (sum, carry_out) = ripple(x, y, ci);
+    if carry_out && !ci && !neac {
+        // The carry propagates around the ring into bit 1
+        ripple(x, y, true).0
+    } else {
+        sum
+    }
+}
+
+/// Propagate `carry_in` through the 16 full adders
+///
+/// Returns the sum and the carry out of bit 16.
+fn ripple(x: W16, y: W16, carry_in: bool) -> (W16, bool) {
+    let mut sum = W16::zero();
+    let mut carry = carry_in;
+    for bit in 0..16 {
+        let (a, b) = (x.get(bit), y.get(bit));
+        sum.set(bit, a ^ b ^ carry);
+        carry = (a && b) || (carry && (a ^ b));
+    }
+    (sum, carry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One's complement sum, computed on the values of the words: the
+    /// arithmetic is modulo 2^16 - 1, and a zero sum is -0 unless both
+    /// operands are +0
+    fn ones_complement(x: u16, y: u16) -> u16 {
+        let value = |word: u16| {
+            if word & 0o100000 != 0 {
+                -i32::from(!word)
+            } else {
+                i32::from(word)
+            }
+        };
+        if x == 0 && y == 0 {
+            return 0;
+        }
+        match (value(x) + value(y)).rem_euclid(0o177777) {
+            0 => 0o177777,
+            sum => sum as u16,
+        }
+    }
+
+    /// One's complement sum plus CI
+    ///
+    /// CI and the end-around carry share the carry input of bit 1, so one
+    /// is only added when the sum does not carry out of bit 16.
+    fn ones_complement_plus_one(x: u16, y: u16) -> u16 {
+        let sum = ones_complement(x, y);
+        if u32::from(x) + u32::from(y) > 0xFFFF {
+            sum
+        } else {
+            sum.wrapping_add(1)
+        }
+    }
+
+    /// Two's complement sum, with CI added to bit 1
+    fn twos_complement(x: u16, y: u16, ci: bool) -> u16 {
+        x.wrapping_add(y).wrapping_add(u16::from(ci))
+    }
+
+    fn check(x: u16, y: u16) {
+        let sums = [
+            (false, false, ones_complement(x, y)),
+            (true, false, ones_complement_plus_one(x, y)),
+            (false, true, twos_complement(x, y, false)),
+            (true, true, twos_complement(x, y, true)),
+        ];
+        for &(ci, neac, expected) in sums.iter() {
+            assert_eq!(
+                add(W16::from(x), W16::from(y), ci, neac),
+                W16::from(expected),
+                "{:06o} + {:06o}, CI {}, NEAC {}",
+                x,
+                y,
+                ci,
+                neac
+            );
+        }
+    }
+
+    /// Every value of X against the edge values and a spread of Y
+    #[test]
+    fn adder_reference() {
+        let edges = [
+            0o000000, 0o000001, 0o037777, 0o040000, 0o077777, 0o100000, 0o137777, 0o140000,
+            0o177776, 0o177777,
+        ];
+        for x in 0..=0xFFFF {
+            for &y in edges.iter() {
+                check(x, y);
+            }
+            for y in (x % 4099..=0xFFFF).step_by(4099) {
+                check(x, y);
+            }
+        }
+    }
+
+    /// Every pair of X and Y, run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn adder_exhaustive() {
+        for x in 0..=0xFFFF {
+            for y in 0..=0xFFFF {
+                check(x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn adder_ones_complement() {
+        let add = |x: u16, y: u16| add(W16::from(x), W16::from(y), false, false);
+
+        assert_eq!(add(0o000005, 0o177774), W16::from(0o000002)); // 5 + -3
+        assert_eq!(add(0o177772, 0o000003), W16::from(0o177775)); // -5 + 3
+        assert_eq!(add(0o000000, 0o177777), W16::from(0o177777)); // +0 + -0
+        assert_eq!(add(0o177777, 0o177777), W16::from(0o177777)); // -0 + -0
+        assert_eq!(add(0o000001, 0o177776), W16::from(0o177777)); // 1 + -1
+    }
+
+    #[test]
+    fn adder_overflow() {
+        let add = |x: u16, y: u16| add(W16::from(x), W16::from(y), false, false);
+
+        // Positive overflow: bit 16 is clear and bit 15 is set
+        assert_eq!(add(0o037777, 0o000001), W16::from(0o040000));
+        assert_eq!(add(0o020000, 0o020000), W16::from(0o040000));
+        // Negative overflow: bit 16 is set and bit 15 is clear
+        assert_eq!(add(0o140000, 0o177776), W16::from(0o137777));
+        assert_eq!(add(0o157777, 0o157777), W16::from(0o137777));
+    }
+
+    #[test]
+    fn adder_carry() {
+        // CI and the end-around carry share the carry input of bit 1
+        assert_eq!(
+            add(W16::from(0o177777), W16::from(0o177777), true, false),
+            W16::from(0o177777)
+        );
+        assert_eq!(
+            add(W16::from(0o177777), W16::zero(), true, false),
+            W16::zero()
+        );
+        assert_eq!(
+            add(W16::from(0o177777), W16::from(0o000001), false, false),
+            W16::from(0o000001)
+        );
+
+        // Two's complement when the end-around carry is inhibited
+        assert_eq!(
+            add(W16::from(0o177777), W16::from(0o000001), false, true),
+            W16::zero()
+        );
+        assert_eq!(
+            add(W16::from(0o177777), W16::from(0o177777), true, true),
+            W16::from(0o177777)
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use crate::cpu::scaler::TIMEPULSES_PER_CENTISECOND;
+use crate::word::W15;
+
+/// Channel reporting the cause of the last hardware restart
+pub const RESTART_CHANNEL: u16 = 0o77;
+
+/// Hardware alarms, which cause a GOJAM restart
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Alarm {
+    /// Parity failure on a word read from erasable memory
+    ErasableParity,
+    /// Parity failure on a word read from fixed memory
+    FixedParity,
+    /// Only TC and TCF, or no TC and TCF at all, were executed for too long
+    TcTrap,
+    /// An interrupt lasted too long, or no interrupt occurred for too long
+    RuptLock,
+    /// NEWJOB (address 67) was not accessed for too long
+    NightWatchman,
+}
+
+impl Alarm {
+    /// Bits set in channel 77 when the alarm occurs
+    ///
+    /// Bit 1 is set on any parity failure, bit 2 tells it occurred in
+    /// erasable memory.
+    pub fn channel_bits(self) -> W15 {
+        match self {
+            Alarm::ErasableParity => W15::from(0o000003),
+            Alarm::FixedParity => W15::from(0o000001),
+            Alarm::TcTrap => W15::from(0o000004),
+            Alarm::RuptLock => W15::from(0o000010),
+            Alarm::NightWatchman => W15::from(0o000020),
+        }
+    }
+}
+
+/// Address of NEWJOB, which is monitored by the Night Watchman
+pub const NEWJOB: u16 = 0o67;
+
+/// Period of the TC Trap check (10 ms)
+pub const TC_TRAP_PERIOD: u64 = TIMEPULSES_PER_CENTISECOND;
+/// Period of the Rupt Lock check (160 ms)
+pub const RUPT_LOCK_PERIOD: u64 = 16 * TIMEPULSES_PER_CENTISECOND;
+/// Period of the Night Watchman check (1.28 s)
+pub const NIGHT_WATCHMAN_PERIOD: u64 = 128 * TIMEPULSES_PER_CENTISECOND;
+
+/// Night Watchman, TC Trap and Rupt Lock circuits
+///
+/// Each circuit records the events it monitors in flip-flops, which are
+/// checked by the scaler once per period, and reset half a period later.
+/// The alarm is thus raised between half a period and one and a half
+/// periods after the last event.
+#[derive(Copy, Clone, Default)]
+pub struct Watchdogs {
+    /// NEWJOB was accessed
+    newjob: bool,
+    /// TC or TCF was executed
+    transfer_control: bool,
+    /// Another instruction was executed
+    other_instruction: bool,
+    /// Time was spent inside an interrupt
+    inside_rupt: bool,
+    /// Time was spent outside an interrupt
+    outside_rupt: bool,
+}
+
+impl Watchdogs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a memory cycle on NEWJOB
+    pub fn access_newjob(&mut self) {
+        self.newjob = true;
+    }
+
+    /// Record the execution of an instruction
+    pub fn instruction(&mut self, transfer_control: bool) {
+        if transfer_control {
+            self.transfer_control = true;
+        } else {
+            self.other_instruction = true;
+        }
+    }
+
+    /// Record whether an interrupt is in progress during a timepulse
+    pub fn rupt(&mut self, in_progress: bool) {
+        if in_progress {
+            self.inside_rupt = true;
+        } else {
+            self.outside_rupt = true;
+        }
+    }
+
+    /// Flip-flops packed in the low bits of a byte, in declaration order
+    pub fn to_bits(self) -> u8 {
+        [
+            self.newjob,
+            self.transfer_control,
+            self.other_instruction,
+            self.inside_rupt,
+            self.outside_rupt,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, &set)| bits | ((set as u8) << bit))
+    }
+
+    /// Flip-flops unpacked from `to_bits`
+    pub fn from_bits(bits: u8) -> Self {
+        let get = |bit: u8| bits & (1 << bit) != 0;
+        Self {
+            newjob: get(0),
+            transfer_control: get(1),
+            other_instruction: get(2),
+            inside_rupt: get(3),
+            outside_rupt: get(4),
+        }
+    }
+
+    /// Check the flip-flops which are due after the given number of
+    /// timepulses since power-on
+    pub fn tick(&mut self, timepulses: u64) -> Option<Alarm> {
+        let mut alarm = None;
+
+        match timepulses % NIGHT_WATCHMAN_PERIOD {
+            0 if !self.newjob => alarm = Some(Alarm::NightWatchman),
+            phase if phase == NIGHT_WATCHMAN_PERIOD / 2 => self.newjob = false,
+            _ => (),
+        }
+
+        match timepulses % RUPT_LOCK_PERIOD {
+            0 if !self.inside_rupt || !self.outside_rupt => alarm = Some(Alarm::RuptLock),
+            phase if phase == RUPT_LOCK_PERIOD / 2 => {
+                self.inside_rupt = false;
+                self.outside_rupt = false;
+            }
+            _ => (),
+        }
+
+        match timepulses % TC_TRAP_PERIOD {
+            0 if !self.transfer_control || !self.other_instruction => alarm = Some(Alarm::TcTrap),
+            phase if phase == TC_TRAP_PERIOD / 2 => {
+                self.transfer_control = false;
+                self.other_instruction = false;
+            }
+            _ => (),
+        }
+
+        alarm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alarm_channel_bits() {
+        assert_eq!(Alarm::ErasableParity.channel_bits(), W15::from(0o3));
+        assert_eq!(Alarm::FixedParity.channel_bits(), W15::from(0o1));
+        assert_eq!(Alarm::NightWatchman.channel_bits(), W15::from(0o20));
+    }
+
+    /// Run the watchdogs from `start` to `end` timepulses, calling `event`
+    /// at every timepulse
+    fn run(
+        watchdogs: &mut Watchdogs,
+        start: u64,
+        end: u64,
+        event: impl Fn(&mut Watchdogs, u64),
+    ) -> Option<(u64, Alarm)> {
+        for timepulses in start + 1..=end {
+            event(watchdogs, timepulses);
+            if let Some(alarm) = watchdogs.tick(timepulses) {
+                return Some((timepulses, alarm));
+            }
+        }
+        None
+    }
+
+    /// Keep TC Trap and Rupt Lock quiet
+    fn busy(watchdogs: &mut Watchdogs, timepulses: u64) {
+        watchdogs.instruction(timepulses.is_multiple_of(2));
+        watchdogs.rupt(timepulses.is_multiple_of(4));
+    }
+
+    /// Returns the time of the Night Watchman alarm when NEWJOB is last
+    /// accessed at `last_access`
+    fn night_watchman_alarm(last_access: u64) -> Option<(u64, Alarm)> {
+        let mut watchdogs = Watchdogs::new();
+        run(&mut watchdogs, 0, 4 * NIGHT_WATCHMAN_PERIOD, |w, t| {
+            busy(w, t);
+            if t <= last_access {
+                w.access_newjob();
+            }
+        })
+    }
+
+    #[test]
+    fn night_watchman() {
+        let half = NIGHT_WATCHMAN_PERIOD / 2;
+
+        // Last access just before a reset, the alarm occurs half a period later
+        assert_eq!(
+            night_watchman_alarm(3 * half - 1),
+            Some((4 * half, Alarm::NightWatchman))
+        );
+        // Last access just after a reset, the alarm occurs one and a half periods later
+        assert_eq!(
+            night_watchman_alarm(3 * half + 1),
+            Some((6 * half, Alarm::NightWatchman))
+        );
+    }
+
+    #[test]
+    fn tc_trap() {
+        let mut watchdogs = Watchdogs::new();
+        assert_eq!(run(&mut watchdogs, 0, 10 * TC_TRAP_PERIOD, busy), None);
+
+        // Only TC
+        let alarm = run(
+            &mut watchdogs,
+            10 * TC_TRAP_PERIOD,
+            20 * TC_TRAP_PERIOD,
+            |w, t| {
+                w.instruction(true);
+                w.rupt(t.is_multiple_of(4));
+            },
+        );
+        assert_eq!(alarm, Some((11 * TC_TRAP_PERIOD, Alarm::TcTrap)));
+    }
+
+    #[test]
+    fn rupt_lock() {
+        let mut watchdogs = Watchdogs::new();
+        assert_eq!(run(&mut watchdogs, 0, 2 * RUPT_LOCK_PERIOD, busy), None);
+
+        // Stuck inside an interrupt
+        let alarm = run(
+            &mut watchdogs,
+            2 * RUPT_LOCK_PERIOD,
+            4 * RUPT_LOCK_PERIOD,
+            |w, t| {
+                w.instruction(t.is_multiple_of(2));
+                w.rupt(true);
+            },
+        );
+        assert_eq!(alarm, Some((3 * RUPT_LOCK_PERIOD, Alarm::RuptLock)));
+    }
+}
@@ -1,5 +1,8 @@
+use crate::cpu::counters::{self, CounterCell, CounterInstruction};
+use crate::cpu::interrupts::BRUPT;
 use crate::cpu::registers::{AddressRegister, MemoryAddress};
-use crate::cpu::Cpu;
+use crate::cpu::{Cpu, Interrupt, MemoryAccess};
+use crate::memory::MemoryWord;
 use crate::word::*;
 
 type WriteLine = W16;
@@ -9,6 +12,7 @@ type WriteLine = W16;
 /// For simulation purposes, each control pulse is divided in two steps:
 /// - Execute operations that modifies the WL
 /// - Execute operations that reads from the WL
+///
 /// The majority of control pulses will implement only one of the two steps.
 pub struct ControlPulse {
     /// Mnemonic of the control pulse
@@ -21,6 +25,17 @@ pub struct ControlPulse {
     pub exec_read_wl: fn(&mut Cpu, WriteLine),
 }
 
+/// Read bits 16 through 1 of register A into bit positions 16 through 1 of register X.
+///
+/// This is a direct transfer that does not use the WL's.
+pub static A2X: ControlPulse = ControlPulse {
+    name: "A2X",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, _wl| {
+        cpu.x = cpu.a;
+    },
+};
+
 /// Insert carry bit into bit position 1 of the adder.
 pub static CI: ControlPulse = ControlPulse {
     name: "CI",
@@ -31,6 +46,48 @@ pub static CI: ControlPulse = ControlPulse {
     exec_read_wl: exec_read_wl_null,
 };
 
+/// Advance the divide stage counter: at next T12, the stage counter goes to the next state of
+/// the sequence 000, 001, 011, 111, 110, 100.
+pub static DVST: ControlPulse = ControlPulse {
+    name: "DVST",
+    exec_write_wl: |cpu| {
+        let st = cpu.st.as_u16();
+        cpu.next_st = W3::from((st << 1) | (!st >> 2 & 0b001));
+        W16::zero()
+    },
+    exec_read_wl: exec_read_wl_null,
+};
+
+/// Clear register X, then write logic ONE's into bit positions 16 through 2.
+pub static MONEX: ControlPulse = ControlPulse {
+    name: "MONEX",
+    exec_write_wl: exec_write_wl_clear_x,
+    exec_read_wl: |cpu, _wl| {
+        cpu.x |= 0o177776u16;
+    },
+};
+
+/// Reset the request of the interrupt being serviced, latched by RRPA.
+pub static KRPT: ControlPulse = ControlPulse {
+    name: "KRPT",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, _wl| {
+        if let Some(interrupt) = cpu.vectored_interrupt.take() {
+            cpu.interrupts.clear(interrupt);
+        }
+    },
+};
+
+/// Inhibit end-around carry until next WY.
+pub static NEAC: ControlPulse = ControlPulse {
+    name: "NEAC",
+    exec_write_wl: |cpu| {
+        cpu.neac = true;
+        W16::zero()
+    },
+    exec_read_wl: exec_read_wl_null,
+};
+
 /// Load next instruction into register SQ at next T12.
 ///
 /// Also frees certain restrictions; permits execution of instruction RUPT and counter instructions.
@@ -47,9 +104,56 @@ pub static NISQ: ControlPulse = ControlPulse {
 /// Clear register X, then enter a logic ONE into bit position 1.
 pub static PONEX: ControlPulse = ControlPulse {
     name: "PONEX",
+    exec_write_wl: exec_write_wl_clear_x,
+    exec_read_wl: |cpu, _wl| {
+        cpu.x |= 0o1u16;
+    },
+};
+
+/// Clear register X, then enter a logic ONE into bit position 2.
+pub static PTWOX: ControlPulse = ControlPulse {
+    name: "PTWOX",
+    exec_write_wl: exec_write_wl_clear_x,
+    exec_read_wl: |cpu, _wl| {
+        cpu.x |= 0o2u16;
+    },
+};
+
+/// Send a plus output pulse for the counter being serviced by DINC.
+///
+/// Output pulses are not emulated.
+pub static POUT: ControlPulse = ControlPulse {
+    name: "POUT",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: exec_read_wl_null,
+};
+
+/// Send a minus output pulse for the counter being serviced by DINC.
+///
+/// Output pulses are not emulated.
+pub static MOUT: ControlPulse = ControlPulse {
+    name: "MOUT",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: exec_read_wl_null,
+};
+
+/// Send a zero output pulse for the counter being serviced by DINC.
+///
+/// For TIME6, this requests T6RUPT and disables the counter by resetting channel 13 bit 15.
+/// Other output pulses are not emulated.
+pub static ZOUT: ControlPulse = ControlPulse {
+    name: "ZOUT",
     exec_write_wl: exec_write_wl_null,
     exec_read_wl: |cpu, _wl| {
-        cpu.x = W16::from(1);
+        if let Some((cell, _)) = cpu.counter {
+            if cell == counters::TIME6 {
+                cpu.interrupts.request(Interrupt::T6);
+
+                let mut channel13 = cpu.channels.read(W9::from(0o13));
+                channel13.set(14, false);
+                cpu.channels.write(W9::from(0o13), channel13);
+            }
+        }
     },
 };
 
@@ -119,6 +223,13 @@ pub static RC: ControlPulse = ControlPulse {
     exec_read_wl: exec_read_wl_null,
 };
 
+/// Place octal 15 on WL's.
+pub static R15: ControlPulse = ControlPulse {
+    name: "R15",
+    exec_write_wl: |_cpu| W16::from(0o15),
+    exec_read_wl: exec_read_wl_null,
+};
+
 /// Place octal 1 on WL's.
 pub static RB1: ControlPulse = ControlPulse {
     name: "RB1",
@@ -126,17 +237,45 @@ pub static RB1: ControlPulse = ControlPulse {
     exec_read_wl: exec_read_wl_null,
 };
 
-/// Read the contents of the input or output channel specified by the contents of register S; bit
-/// 16 is read to WL's 16 and 15 and bits 14 through 1 are read to WL's 14 through 1.
+/// Place octal 2 on WL's.
+pub static RB2: ControlPulse = ControlPulse {
+    name: "RB2",
+    exec_write_wl: |_cpu| W16::from(0o2),
+    exec_read_wl: exec_read_wl_null,
+};
+
+/// Read the content of the input channel whose address is in register S. Bits 15 through 1 are
+/// read to WL's 15 through 1 and bit 15 is also read to WL 16.
+///
+/// Channels 1 and 2 are registers L and Q, whose bits 16 through 1 are read to WL's 16 through 1.
 pub static RCH: ControlPulse = ControlPulse {
     name: "RCH",
-    exec_write_wl: |_cpu| {
-        // TODO
-        W16::zero()
+    exec_write_wl: |cpu| {
+        let channel = W9::from(cpu.s.inner());
+        cpu.accesses.push(MemoryAccess::ChannelRead(channel));
+        match channel.as_u16() {
+            0o1 => cpu.l,
+            0o2 => cpu.q,
+            _ => MemoryWord::with_proper_parity(cpu.input_channel(channel)).as_register_value(),
+        }
     },
     exec_read_wl: exec_read_wl_null,
 };
 
+/// Read bits 16 through 1 of register L to WL's 16 through 1.
+pub static RL: ControlPulse = ControlPulse {
+    name: "RL",
+    exec_write_wl: |cpu| cpu.l,
+    exec_read_wl: exec_read_wl_null,
+};
+
+/// Read bits 16 through 1 of register Q to WL's 16 through 1.
+pub static RQ: ControlPulse = ControlPulse {
+    name: "RQ",
+    exec_write_wl: |cpu| cpu.q,
+    exec_read_wl: exec_read_wl_null,
+};
+
 /// Read bits 16 through 1 of register G to WL's 16 through 1.
 pub static RG: ControlPulse = ControlPulse {
     name: "RG",
@@ -174,6 +313,16 @@ pub static RSC: ControlPulse = ControlPulse {
     exec_read_wl: exec_read_wl_null,
 };
 
+/// Read the address of the counter being serviced to WL's.
+pub static RSCT: ControlPulse = ControlPulse {
+    name: "RSCT",
+    exec_write_wl: |cpu| match cpu.counter {
+        Some((cell, _)) => W16::from(cell.address()),
+        None => W16::zero(),
+    },
+    exec_read_wl: exec_read_wl_null,
+};
+
 /// Place octal 4000 (start address) on WL's.
 pub static RSTRT: ControlPulse = ControlPulse {
     name: "RSTRT",
@@ -181,6 +330,23 @@ pub static RSTRT: ControlPulse = ControlPulse {
     exec_read_wl: exec_read_wl_null,
 };
 
+/// Read the address of the interrupt service routine of the highest priority pending interrupt
+/// to WL's. The interrupt is latched, so that KRPT resets its request.
+///
+/// RUPT1 is not executed when no interrupt is pending, see `Cpu::try_current_subinstruction`.
+pub static RRPA: ControlPulse = ControlPulse {
+    name: "RRPA",
+    exec_write_wl: |cpu| {
+        let interrupt = cpu
+            .interrupts
+            .highest()
+            .expect("RRPA without a pending interrupt");
+        cpu.vectored_interrupt = Some(interrupt);
+        W16::from(interrupt.vector())
+    },
+    exec_read_wl: exec_read_wl_null,
+};
+
 /// Read bits 16 through 1 of adder output gates (U) to WL's 16 through 1.
 pub static RU: ControlPulse = ControlPulse {
     name: "RU",
@@ -188,6 +354,21 @@ pub static RU: ControlPulse = ControlPulse {
     exec_read_wl: exec_read_wl_null,
 };
 
+/// Read bits 15 through 1 of adder output gates (U) to WL's 15 through 1. Bit 15 is also read
+/// to WL 16.
+pub static RUS: ControlPulse = ControlPulse {
+    name: "RUS",
+    exec_write_wl: |cpu| {
+        let u = cpu.u() & 0o77777u16;
+        if u.get(14) {
+            u | 0o100000u16
+        } else {
+            u
+        }
+    },
+    exec_read_wl: exec_read_wl_null,
+};
+
 /// Read bits 16 through 1 of register Z to WL's 16 through 1.
 pub static RZ: ControlPulse = ControlPulse {
     name: "RZ",
@@ -196,7 +377,6 @@ pub static RZ: ControlPulse = ControlPulse {
 };
 
 /// Set stage 1 flip-flop to logic ONE at next T12.
-#[allow(dead_code)]
 pub static ST1: ControlPulse = ControlPulse {
     name: "ST1",
     exec_write_wl: exec_write_wl_null,
@@ -253,6 +433,18 @@ pub static TPZG: ControlPulse = ControlPulse {
     },
 };
 
+/// Test for RESUME: if register S contains octal 17 and the instruction is not extended, set stage
+/// 2 flip-flop to logic ONE at next T12, so that RSM3 follows NDX0.
+pub static TRSM: ControlPulse = ControlPulse {
+    name: "TRSM",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, _wl| {
+        if cpu.s.inner() == W12::from(BRUPT) && !cpu.sq.is_extended() {
+            cpu.next_st |= 0b010u16;
+        }
+    },
+};
+
 /// Test sign (bit 16): if a logic ZERO, set flip-flop BR1 to logic ZERO; if a logic ONE, set
 /// flip-flop BR1 to logic ONE.
 pub static TSGN: ControlPulse = ControlPulse {
@@ -263,6 +455,27 @@ pub static TSGN: ControlPulse = ControlPulse {
     },
 };
 
+/// Test sign (bit 16): copy the sign into flip-flop BR2.
+pub static TSGN2: ControlPulse = ControlPulse {
+    name: "TSGN2",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, wl| {
+        cpu.br.set_br2(wl.get(15));
+    },
+};
+
+/// Test sign of the adder output: copy bit 16 of U into flip-flop BR1.
+///
+/// This is a direct transfer that does not use the WL's.
+pub static TSGU: ControlPulse = ControlPulse {
+    name: "TSGU",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, _wl| {
+        let u = cpu.u();
+        cpu.br.set_br1(u.get(15));
+    },
+};
+
 /// Clear register A and write the contents of WL's 16 through 1 into bit positions 16 through 1.
 pub static WA: ControlPulse = ControlPulse {
     name: "WA",
@@ -281,39 +494,91 @@ pub static WB: ControlPulse = ControlPulse {
     },
 };
 
-/// Clear the channel specified by the contents of register S (bits 9 through 1) and write the
-/// contents of WL's 16 through 1 into this channel.
+/// Write the contents of WL's 16 and 14 through 1 into bit positions 15 and 14 through 1 of the
+/// output channel whose address is in register S.
+///
+/// Channels 1 and 2 are registers L and Q, where WL's 16 through 1 are written to bits 16
+/// through 1.
 pub static WCH: ControlPulse = ControlPulse {
     name: "WCH",
     exec_write_wl: exec_write_wl_null,
-    exec_read_wl: |_cpu, _wl| {
-        // TODO
+    exec_read_wl: |cpu, wl| {
+        let channel = W9::from(cpu.s.inner());
+        cpu.accesses.push(MemoryAccess::ChannelWrite(channel));
+        match channel.as_u16() {
+            0o1 => cpu.l = wl,
+            0o2 => cpu.q = wl,
+            _ => cpu.output_channel(channel, MemoryWord::from_register_value(wl).value()),
+        }
     },
 };
 
 /// Clear register G and write the contents of WL's 16 through 1 into bit positions 16 through 1
 /// (except if register S contains octal addresses 20 through 23).
+///
+/// If S contains an editing address, the WL's are edited as follows, and bit 15 of G is also
+/// written to bit 16 so that the edited word is stored with its sign:
+/// - 20 (CYR): WL's 15 through 2 into bits 14 through 1, WL 1 into bit 15 (cycle right)
+/// - 21 (SR): WL's 15 through 2 into bits 14 through 1, WL 16 into bit 15 (shift right)
+/// - 22 (CYL): WL's 14 through 1 into bits 15 through 2, WL 16 into bit 1 (cycle left)
+/// - 23 (EDOP): WL's 14 through 8 into bits 7 through 1, other bits cleared
 pub static WG: ControlPulse = ControlPulse {
     name: "WG",
     exec_write_wl: exec_write_wl_null,
     exec_read_wl: |cpu, wl| {
-        // TODO: Check if S contains octal address 20 through 23
+        let edited = match cpu.s.inner().as_u16() {
+            0o20 => Some(((wl >> 1) & 0o037777u16) | ((wl & 0o1u16) << 14)),
+            0o21 => Some(((wl >> 1) & 0o037777u16) | ((wl >> 1) & 0o040000u16)),
+            0o22 => Some(((wl << 1) & 0o077776u16) | ((wl >> 15) & 0o1u16)),
+            0o23 => Some((wl >> 7) & 0o000177u16),
+            _ => None,
+        };
+
+        cpu.g = match edited {
+            Some(mut value) => {
+                value.set(15, value.get(14));
+                value
+            }
+            None => wl,
+        };
+    },
+};
+
+/// Clear register G and write the contents of WL's 16 through 1 into bit positions 16 through 1,
+/// without the editing performed by WG.
+pub static WGN: ControlPulse = ControlPulse {
+    name: "WGN",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, wl| {
         cpu.g = wl;
     },
 };
 
+/// Clear register L and write the contents of WL's 16 through 1 into bit positions 16 through 1.
+pub static WL: ControlPulse = ControlPulse {
+    name: "WL",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, wl| {
+        cpu.l = wl;
+    },
+};
+
 /// Test for positive overflow. If register S contains 0025, counter 0024 is incremented; if
 /// register S contains 0026, 0027, or 0030, instruction RUPT is executed.
 pub static WOVR: ControlPulse = ControlPulse {
     name: "WOVR",
     exec_write_wl: exec_write_wl_null,
     exec_read_wl: |cpu, wl| {
-        if (wl & 0b1_100_000_000_000_000u16) == W16::from(0b0_100_000_000_000_000)
-            && (cpu.s.inner() == W12::from(0o0026)
-                || cpu.s.inner() == W12::from(0o0027)
-                || cpu.s.inner() == W12::from(0o0030))
-        {
-            // TODO: Request RUPT
+        if (wl & 0b1_100_000_000_000_000u16) == W16::from(0b0_100_000_000_000_000) {
+            match CounterCell::new(cpu.s.inner().as_u16()) {
+                Some(counters::TIME1) => cpu
+                    .counters
+                    .request(counters::TIME2, CounterInstruction::Pinc),
+                Some(counters::TIME3) => cpu.interrupts.request(Interrupt::T3),
+                Some(counters::TIME4) => cpu.interrupts.request(Interrupt::T4),
+                Some(counters::TIME5) => cpu.interrupts.request(Interrupt::T5),
+                _ => (),
+            }
         }
     },
 };
@@ -333,8 +598,8 @@ pub static WSC: ControlPulse = ControlPulse {
     name: "WSC",
     exec_write_wl: exec_write_wl_null,
     exec_read_wl: |cpu, wl| {
-        match cpu.s.address() {
-            MemoryAddress::Register(reg) => match reg.as_u16() {
+        if let MemoryAddress::Register(reg) = cpu.s.address() {
+            match reg.as_u16() {
                 0o0 => cpu.a = wl,
                 0o1 => cpu.l = wl,
                 0o2 => cpu.q = wl,
@@ -347,8 +612,7 @@ pub static WSC: ControlPulse = ControlPulse {
                 }
                 0o7 => (), // Do nothing
                 _ => panic!("Unexpected 3-bit value"),
-            },
-            _ => (), // Do nothing
+            }
         }
     },
 };
@@ -363,12 +627,17 @@ pub static WQ: ControlPulse = ControlPulse {
 };
 
 /// Clear registers X and Y and write the contents of WL's 16 through 1 into bit positions 16 through 1
-/// of register Y.
+/// of register Y. Also clears the carry bit.
 pub static WY: ControlPulse = ControlPulse {
     name: "WY",
-    exec_write_wl: exec_write_wl_null,
-    exec_read_wl: |cpu, wl| {
+    exec_write_wl: |cpu| {
         cpu.x = W16::zero();
+        cpu.y = W16::zero();
+        cpu.ci = false;
+        cpu.neac = false;
+        W16::zero()
+    },
+    exec_read_wl: |cpu, wl| {
         cpu.y = wl;
     },
 };
@@ -381,6 +650,7 @@ pub static WY12: ControlPulse = ControlPulse {
         cpu.x = W16::zero();
         cpu.y = W16::zero();
         cpu.ci = false;
+        cpu.neac = false;
         W16::zero()
     },
     exec_read_wl: |cpu, wl| {
@@ -388,6 +658,22 @@ pub static WY12: ControlPulse = ControlPulse {
     },
 };
 
+/// Clear registers X and Y; write the contents of WL's 15 through 1 into bit positions 16 through 2
+/// of register Y. Also clears the carry bit.
+pub static WYD: ControlPulse = ControlPulse {
+    name: "WYD",
+    exec_write_wl: |cpu| {
+        cpu.x = W16::zero();
+        cpu.y = W16::zero();
+        cpu.ci = false;
+        cpu.neac = false;
+        W16::zero()
+    },
+    exec_read_wl: |cpu, wl| {
+        cpu.y = wl << 1;
+    },
+};
+
 /// Clear register Z and write the contents of WL's 16 through 1 into bit positions 16 through 1.
 pub static WZ: ControlPulse = ControlPulse {
     name: "WZ",
@@ -398,8 +684,16 @@ pub static WZ: ControlPulse = ControlPulse {
 };
 
 // Helper functions
-fn exec_write_wl_null(_cpu: &mut Cpu) -> WriteLine {
+pub(super) fn exec_write_wl_null(_cpu: &mut Cpu) -> WriteLine {
     W16::zero()
 }
 
 fn exec_read_wl_null(_cpu: &mut Cpu, _wl: WriteLine) {}
+
+// Registers are cleared before anything is written into them, so
+// the clearing is done in the first step. This allows multiple control
+// pulses to write into the same register during the same time pulse.
+fn exec_write_wl_clear_x(cpu: &mut Cpu) -> WriteLine {
+    cpu.x = W16::zero();
+    W16::zero()
+}
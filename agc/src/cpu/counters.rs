@@ -0,0 +1,179 @@
+use crate::cpu::registers::{AddressRegister, BranchRegister};
+use crate::word::W16;
+use std::collections::VecDeque;
+
+/// Address of the first counter cell (TIME2)
+pub const FIRST_COUNTER: u16 = 0o24;
+/// Address of the last counter cell (ALTM)
+pub const LAST_COUNTER: u16 = 0o60;
+
+/// Address of a counter cell, between octal 24 and 60
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CounterCell(u16);
+
+impl CounterCell {
+    /// Returns the counter cell at `address`, or `None` if the address is not
+    /// between octal 24 and 60
+    pub fn new(address: u16) -> Option<Self> {
+        if (FIRST_COUNTER..=LAST_COUNTER).contains(&address) {
+            Some(Self(address))
+        } else {
+            None
+        }
+    }
+
+    /// Erasable address of the cell
+    pub fn address(self) -> u16 {
+        self.0
+    }
+}
+
+/// Counter cells
+pub const TIME2: CounterCell = CounterCell(0o24);
+pub const TIME1: CounterCell = CounterCell(0o25);
+pub const TIME3: CounterCell = CounterCell(0o26);
+pub const TIME4: CounterCell = CounterCell(0o27);
+pub const TIME5: CounterCell = CounterCell(0o30);
+pub const TIME6: CounterCell = CounterCell(0o31);
+pub const CDUX: CounterCell = CounterCell(0o32);
+pub const CDUY: CounterCell = CounterCell(0o33);
+pub const CDUZ: CounterCell = CounterCell(0o34);
+pub const OPTY: CounterCell = CounterCell(0o35);
+pub const OPTX: CounterCell = CounterCell(0o36);
+pub const PIPAX: CounterCell = CounterCell(0o37);
+pub const PIPAY: CounterCell = CounterCell(0o40);
+pub const PIPAZ: CounterCell = CounterCell(0o41);
+pub const INLINK: CounterCell = CounterCell(0o45);
+pub const OUTLINK: CounterCell = CounterCell(0o57);
+
+/// Involuntary instructions executed on a counter cell
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CounterInstruction {
+    /// Add +1 in one's complement
+    Pinc,
+    /// Add -1 in one's complement
+    Minc,
+    /// Diminish the magnitude by 1
+    Dinc,
+    /// Add +1 in two's complement
+    Pcdu,
+    /// Add -1 in two's complement
+    Mcdu,
+    /// Shift left
+    Shinc,
+    /// Shift left and add 1
+    Shanc,
+}
+
+/// Pending counter requests
+///
+/// Each request results in one counter subinstruction. Counters at lower
+/// addresses have priority. Like the hardware, which has one pending
+/// flip-flop per cell and kind of request, a request is merged with an
+/// identical request still pending on the same cell.
+#[derive(Clone)]
+pub struct CounterRequests {
+    pending: Vec<VecDeque<CounterInstruction>>,
+}
+
+impl Default for CounterRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CounterRequests {
+    pub fn new() -> Self {
+        Self {
+            pending: vec![VecDeque::new(); (LAST_COUNTER - FIRST_COUNTER + 1) as usize],
+        }
+    }
+
+    pub fn request(&mut self, cell: CounterCell, instruction: CounterInstruction) {
+        let requests = &mut self.pending[(cell.address() - FIRST_COUNTER) as usize];
+        if !requests.contains(&instruction) {
+            requests.push_back(instruction);
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.iter().any(|requests| !requests.is_empty())
+    }
+
+    /// All the pending requests, in order of priority
+    pub fn iter(&self) -> impl Iterator<Item = (CounterCell, CounterInstruction)> + '_ {
+        self.pending
+            .iter()
+            .enumerate()
+            .flat_map(|(offset, requests)| {
+                let cell = CounterCell(FIRST_COUNTER + offset as u16);
+                requests.iter().map(move |instruction| (cell, *instruction))
+            })
+    }
+
+    /// Remove the request with the highest priority
+    pub fn take_highest(&mut self) -> Option<(CounterCell, CounterInstruction)> {
+        self.pending
+            .iter_mut()
+            .enumerate()
+            .find_map(|(offset, requests)| {
+                requests
+                    .pop_front()
+                    .map(|instruction| (CounterCell(FIRST_COUNTER + offset as u16), instruction))
+            })
+    }
+}
+
+/// Registers of the interrupted sequence which are used by a counter
+/// subinstruction
+///
+/// The counter address does not come from S, so the sequence resumes
+/// as if the counter subinstruction never happened.
+#[derive(Copy, Clone)]
+pub struct CounterContext {
+    pub s: AddressRegister,
+    pub g: W16,
+    pub x: W16,
+    pub y: W16,
+    pub neac: bool,
+    pub br: BranchRegister,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_priority() {
+        let mut requests = CounterRequests::new();
+        assert!(!requests.is_pending());
+
+        requests.request(PIPAX, CounterInstruction::Minc);
+        requests.request(TIME3, CounterInstruction::Pinc);
+        requests.request(PIPAX, CounterInstruction::Pinc);
+        requests.request(PIPAX, CounterInstruction::Minc);
+        assert!(requests.is_pending());
+
+        assert_eq!(
+            requests.take_highest(),
+            Some((TIME3, CounterInstruction::Pinc))
+        );
+        assert_eq!(
+            requests.take_highest(),
+            Some((PIPAX, CounterInstruction::Minc))
+        );
+        assert_eq!(
+            requests.take_highest(),
+            Some((PIPAX, CounterInstruction::Pinc))
+        );
+        assert_eq!(requests.take_highest(), None);
+    }
+
+    #[test]
+    fn counter_cell() {
+        assert_eq!(CounterCell::new(0o24), Some(TIME2));
+        assert_eq!(CounterCell::new(0o60).map(CounterCell::address), Some(0o60));
+        assert_eq!(CounterCell::new(0o23), None);
+        assert_eq!(CounterCell::new(0o61), None);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::cpu::control_pulses::{exec_write_wl_null, ControlPulse};
+use crate::word::*;
+
+// MP and DV are not implemented with the control pulse sequences of the
+// hardware, see MP0 and DV0. The pulses below only exist in these
+// sequences. They are named after what they do, so that they cannot be
+// mistaken for the hardware pulses listed in `control_pulses`.
+
+/// Clear registers X and Y, transfer the contents of register A into register X and, if bit 1
+/// of register L is a logic ONE, write bits 14 through 1 of register B into register Y. Also
+/// clears the carry bit.
+///
+/// This adds the multiplicand to the partial product according to the next bit of the
+/// multiplier. It stands in for ZIP, which examines two bits of the multiplier.
+pub static MP_ADD: ControlPulse = ControlPulse {
+    name: "MP_ADD",
+    exec_write_wl: |cpu| {
+        cpu.x = W16::zero();
+        cpu.y = W16::zero();
+        cpu.ci = false;
+        cpu.neac = false;
+        W16::zero()
+    },
+    exec_read_wl: |cpu, _wl| {
+        cpu.x = cpu.a;
+        if cpu.l.get(0) {
+            cpu.y = cpu.b & 0o037777u16;
+        }
+    },
+};
+
+/// Read bits 16 through 1 of the adder output gates (U) to WL's 16 through 1, then write WL's 16
+/// through 2 into bit positions 15 through 1 of register A. Register L is shifted right by one
+/// position and WL 1 is written into bit position 14 of register L.
+///
+/// This shifts the partial product held in A and L by one bit. It stands in for ZAP.
+pub static MP_SHIFT: ControlPulse = ControlPulse {
+    name: "MP_SHIFT",
+    exec_write_wl: |cpu| cpu.u(),
+    exec_read_wl: |cpu, wl| {
+        cpu.a = wl >> 1;
+        cpu.l = ((cpu.l >> 1) & 0o017777u16) | ((wl & 0o1u16) << 13);
+    },
+};
+
+/// Insert carry bit into bit position 1 of the adder if bit 14 of register L is a logic ZERO.
+///
+/// This shifts the next bit of the dividend into the complemented partial remainder.
+///
+/// This is a direct transfer that does not use the WL's.
+pub static DV_DIVIDEND_BIT: ControlPulse = ControlPulse {
+    name: "DV_DIVIDEND_BIT",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, _wl| {
+        if !cpu.l.get(13) {
+            cpu.ci = true;
+        }
+    },
+};
+
+/// Clear register X if flip-flop BR1 is a logic ZERO.
+///
+/// X holds the divisor, which is only subtracted when the partial remainder is not smaller.
+///
+/// This is a direct transfer that does not use the WL's.
+pub static DV_CLEAR_X: ControlPulse = ControlPulse {
+    name: "DV_CLEAR_X",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, _wl| {
+        if !cpu.br.br1() {
+            cpu.x = W16::zero();
+        }
+    },
+};
+
+/// Shift bits 13 through 1 of register L into bit positions 14 through 2 and enter the content
+/// of flip-flop BR1 into bit position 1. Bits 16 and 15 are cleared.
+///
+/// This shifts the next bit of the quotient into L.
+///
+/// This is a direct transfer that does not use the WL's.
+pub static DV_QUOTIENT_BIT: ControlPulse = ControlPulse {
+    name: "DV_QUOTIENT_BIT",
+    exec_write_wl: exec_write_wl_null,
+    exec_read_wl: |cpu, _wl| {
+        let quotient_bit = if cpu.br.br1() { 0o1u16 } else { 0o0u16 };
+        cpu.l = ((cpu.l << 1) & 0o037777u16) | quotient_bit;
+    },
+};
@@ -0,0 +1,36 @@
+use crate::word::{W16, W3, W7};
+use std::fmt;
+
+/// Error stopping the emulation
+///
+/// The CPU is left untouched, at the control pulse which could not be
+/// executed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EmulationError {
+    /// No subinstruction exists for the instruction in SQ at stage ST
+    ///
+    /// Z points after the instruction.
+    UnimplementedOpcode { sq: W7, st: W3, z: W16 },
+    /// The RUPT sequence has no subinstruction at stage ST
+    IllegalStage { st: W3, z: W16 },
+    /// The RUPT sequence reached RRPA while no interrupt is pending
+    NoPendingInterrupt { z: W16 },
+}
+
+impl fmt::Display for EmulationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulationError::UnimplementedOpcode { sq, st, z } => {
+                write!(f, "opcode {} with st {} does not exist (Z = {})", sq, st, z)
+            }
+            EmulationError::IllegalStage { st, z } => {
+                write!(f, "RUPT with st {} does not exist (Z = {})", st, z)
+            }
+            EmulationError::NoPendingInterrupt { z } => {
+                write!(f, "RUPT without a pending interrupt (Z = {})", z)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmulationError {}
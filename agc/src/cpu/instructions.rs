@@ -1,4 +1,5 @@
 use crate::cpu::control_pulses::*;
+use crate::cpu::emulated_pulses::*;
 use crate::cpu::TimePulse;
 use crate::word::W2;
 
@@ -21,6 +22,14 @@ pub struct Subinstruction {
 }
 pub type Actions = &'static [Action];
 
+/// Control pulse conditioned by the content of the branch register
+///
+/// The suffix is compared with the content of the register, BR2 then
+/// BR1. For example, `Br01` is executed only if BR2 is ZERO and BR1 is
+/// ONE. TOV sets the register to 01 on positive overflow, so the
+/// overflow branches are written as in ND-1021042, but the conditions
+/// on BR1 and BR2 set by the other tests have their two characters
+/// swapped.
 pub enum Action {
     BrXX(&'static ControlPulse),
     BrX0(&'static ControlPulse),
@@ -82,75 +91,1032 @@ impl Subinstruction {
     }
 }
 
+/// Every subinstruction, including RUPT and the counter subinstructions
+pub static SUBINSTRUCTIONS: [&Subinstruction; 61] = [
+    &AD0, &ADS0, &AUG0, &BZF0, &BZMF0, &CA0, &CCS0, &CS0, &DAS0, &DAS1, &DCA0, &DCA1, &DCS0, &DCS1,
+    &DIM0, &DV0, &DV1, &DV3, &DV7, &DV6, &DV4, &DXCH0, &DXCH1, &EDRUPT0, &EDRUPT1, &EDRUPT3, &GOJ1,
+    &INCR0, &LXCH0, &MASK0, &MP0, &MP1, &MP3, &MSU0, &NDX0, &NDX1, &QXCH0, &RSM3, &RUPT0, &RUPT1,
+    &RUPT3, &STD2, &SU0, &TC0, &TCF0, &TS0, &XCH0, &PINC, &MINC, &DINC, &PCDU, &MCDU, &SHINC,
+    &SHANC, &WRITE0, &READ0, &RAND0, &WAND0, &ROR0, &WOR0, &RXOR0,
+];
+
+/// Returns true if a subinstruction has this name
+pub fn is_subinstruction_name(name: &str) -> bool {
+    SUBINSTRUCTIONS
+        .iter()
+        .any(|subinstruction| subinstruction.name == name)
+}
+
+/// Returns true if a subinstruction executes a control pulse with this name
+pub fn is_control_pulse_name(name: &str) -> bool {
+    SUBINSTRUCTIONS.iter().any(|s| {
+        [
+            s.t1, s.t2, s.t3, s.t4, s.t5, s.t6, s.t7, s.t8, s.t9, s.t10, s.t11, s.t12,
+        ]
+        .iter()
+        .flat_map(|actions| actions.iter())
+        .any(|action| action.control_pulse().name == name)
+    })
+}
+
+pub static AD0: Subinstruction = Subinstruction {
+    name: "AD0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[Action::BrXX(&RB), Action::BrXX(&WY), Action::BrXX(&A2X)],
+    t11: &[Action::BrXX(&RU), Action::BrXX(&WA)],
+    t12: &[],
+};
+
+pub static ADS0: Subinstruction = Subinstruction {
+    name: "ADS0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WY), Action::BrXX(&A2X)],
+    t6: &[Action::BrXX(&RU), Action::BrXX(&WA)],
+    t7: &[Action::BrXX(&RA), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static AUG0: Subinstruction = Subinstruction {
+    name: "AUG0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WY), Action::BrXX(&TSGN)],
+    t6: &[Action::BrX0(&PONEX), Action::BrX1(&MONEX)],
+    t7: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WSC),
+        Action::BrXX(&WG),
+        Action::BrXX(&WOVR),
+    ],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// BZF and BZMF branch with the same sequence as TCF0. If the branch is not
+// taken, the next instruction is fetched by STD2 as usual.
+pub static BZF0: Subinstruction = Subinstruction {
+    name: "BZF0",
+    t1: &[
+        Action::BrXX(&RA),
+        Action::BrXX(&WG),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+    ],
+    t2: &[Action::BrXX(&TPZG)],
+    t3: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t4: &[Action::Br1X(&RB), Action::Br1X(&WY12), Action::Br1X(&CI)],
+    t5: &[Action::Br1X(&NISQ)],
+    t6: &[Action::Br1X(&RU), Action::Br1X(&WZ)],
+    t7: &[],
+    t8: &[
+        Action::Br0X(&RZ),
+        Action::Br0X(&WS),
+        Action::Br0X(&ST2),
+        Action::Br1X(&RAD),
+        Action::Br1X(&WB),
+        Action::Br1X(&WS),
+    ],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static BZMF0: Subinstruction = Subinstruction {
+    name: "BZMF0",
+    t1: &[
+        Action::BrXX(&RA),
+        Action::BrXX(&WG),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+    ],
+    t2: &[Action::BrXX(&TPZG)],
+    t3: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t4: &[
+        Action::BrX1(&RB),
+        Action::Br10(&RB),
+        Action::BrX1(&WY12),
+        Action::Br10(&WY12),
+        Action::BrX1(&CI),
+        Action::Br10(&CI),
+    ],
+    t5: &[Action::BrX1(&NISQ), Action::Br10(&NISQ)],
+    t6: &[
+        Action::BrX1(&RU),
+        Action::Br10(&RU),
+        Action::BrX1(&WZ),
+        Action::Br10(&WZ),
+    ],
+    t7: &[],
+    t8: &[
+        Action::Br00(&RZ),
+        Action::Br00(&WS),
+        Action::Br00(&ST2),
+        Action::BrX1(&RAD),
+        Action::Br10(&RAD),
+        Action::BrX1(&WB),
+        Action::Br10(&WB),
+        Action::BrX1(&WS),
+        Action::Br10(&WS),
+    ],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
 pub static CA0: Subinstruction = Subinstruction {
     name: "CA0",
     t1: &[],
     t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
     t3: &[],
     t4: &[],
-    t5: &[],
-    t6: &[],
-    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[Action::BrXX(&RB), Action::BrXX(&WA)],
+    t11: &[],
+    t12: &[],
+};
+
+pub static CCS0: Subinstruction = Subinstruction {
+    name: "CCS0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[
+        Action::BrXX(&RG),
+        Action::BrXX(&WB),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+        Action::BrXX(&TPZG),
+    ],
+    t6: &[
+        Action::BrXX(&RZ),
+        Action::BrXX(&WY12),
+        Action::Br1X(&PONEX),
+        Action::BrX1(&PTWOX),
+    ],
+    t7: &[Action::BrXX(&RU), Action::BrXX(&WZ)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[
+        Action::BrX0(&RC),
+        Action::BrX1(&RB),
+        Action::BrXX(&WY),
+        Action::Br0X(&PONEX),
+    ],
+    t11: &[Action::BrXX(&RU), Action::BrXX(&WB)],
+    t12: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+};
+
+pub static CS0: Subinstruction = Subinstruction {
+    name: "CS0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t11: &[],
+    t12: &[],
+};
+
+// DAS and DXCH are encoded with K+1. The address K of the second stage is
+// computed as the complement of -(K+1)+1, so that K+1 = 1 gives +0 (A)
+// instead of -0.
+pub static DAS0: Subinstruction = Subinstruction {
+    name: "DAS0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RA), Action::BrXX(&WY)],
+    t4: &[Action::BrXX(&RL), Action::BrXX(&WA)],
+    t5: &[Action::BrXX(&RU), Action::BrXX(&WL)],
+    t6: &[Action::BrXX(&RG), Action::BrXX(&WY), Action::BrXX(&A2X)],
+    t7: &[Action::BrXX(&RL), Action::BrXX(&WA)],
+    t8: &[Action::BrXX(&WL)],
+    t9: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WSC),
+        Action::BrXX(&WG),
+        Action::BrXX(&TOV),
+    ],
+    t10: &[Action::BrXX(&RL10BB), Action::BrXX(&WB)],
+    t11: &[
+        Action::BrXX(&RC),
+        Action::BrXX(&WY),
+        Action::BrXX(&PONEX),
+        Action::BrXX(&ST1),
+    ],
+    t12: &[Action::BrXX(&RU), Action::BrXX(&WB)],
+};
+
+pub static DAS1: Subinstruction = Subinstruction {
+    name: "DAS1",
+    t1: &[Action::BrXX(&RC), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[
+        Action::BrXX(&WY),
+        Action::BrXX(&A2X),
+        Action::Br01(&RB1),
+        Action::Br10(&R1C),
+    ],
+    t4: &[Action::BrXX(&RU), Action::BrXX(&WA)],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WY), Action::BrXX(&A2X)],
+    t6: &[Action::BrXX(&RU), Action::BrXX(&WB), Action::BrXX(&TOV)],
+    t7: &[Action::BrXX(&WA), Action::Br01(&RB1), Action::Br10(&R1C)],
+    t8: &[Action::BrXX(&RB), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t9: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// DCA and DCS are encoded with K+1. The instruction word is never smaller
+// than octal 30000, so the address K of the second stage can be computed
+// with a single subtraction.
+pub static DCA0: Subinstruction = Subinstruction {
+    name: "DCA0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RB), Action::BrXX(&WY), Action::BrXX(&MONEX)],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WL)],
+    t8: &[Action::BrXX(&RU), Action::BrXX(&WS), Action::BrXX(&ST1)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static DCA1: Subinstruction = Subinstruction {
+    name: "DCA1",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[Action::BrXX(&RB), Action::BrXX(&WA)],
+    t11: &[],
+    t12: &[],
+};
+
+pub static DCS0: Subinstruction = Subinstruction {
+    name: "DCS0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RB), Action::BrXX(&WY), Action::BrXX(&MONEX)],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RC), Action::BrXX(&WL)],
+    t9: &[Action::BrXX(&RU), Action::BrXX(&WS), Action::BrXX(&ST1)],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static DCS1: Subinstruction = Subinstruction {
+    name: "DCS1",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t11: &[],
+    t12: &[],
+};
+
+// DIM brings K one step closer to zero without ever producing -0 from a
+// positive value: a positive K is diminished as the complement of -K+1.
+pub static DIM0: Subinstruction = Subinstruction {
+    name: "DIM0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[
+        Action::BrXX(&RG),
+        Action::BrXX(&WB),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+        Action::BrXX(&TPZG),
+    ],
+    t6: &[
+        Action::Br00(&RC),
+        Action::Br01(&RB),
+        Action::Br0X(&WY),
+        Action::Br0X(&PONEX),
+    ],
+    t7: &[Action::Br0X(&RU), Action::Br0X(&WB)],
+    t8: &[
+        Action::Br00(&RC),
+        Action::Br10(&RB),
+        Action::BrX1(&RB),
+        Action::BrXX(&WSC),
+        Action::BrXX(&WG),
+    ],
+    t9: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// DV divides the magnitude of the dividend in A,L by the magnitude of the
+// divisor K, one bit of the quotient per two time pulses. During the
+// divide loop, A holds the magnitude of the divisor, B the complement of
+// the partial remainder and L the bits of the dividend not yet used, with
+// the bits of the quotient shifted in from the right. The sign of the
+// dividend is kept in BR2 and the divisor is kept in G, so that the memory
+// cycles of each stage write it back unchanged.
+//
+// If A and L have different signs, the dividend is first brought to a
+// common sign. A zero A (resp. L) takes the sign of L (resp. A).
+//
+// This is not the sequence of the hardware. Only the stages DV0, DV1, DV3,
+// DV7, DV6 and DV4 are shared with it: the divide loop is split in 6, 6
+// and 2 bits over DV3, DV7 and DV6, with pulses which do not exist in the
+// hardware (see `emulated_pulses`). Only the quotient and remainder left
+// in A and L at the end of DV4 match the hardware, the registers in
+// between do not.
+pub static DV0: Subinstruction = Subinstruction {
+    name: "DV0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RA), Action::BrXX(&WB), Action::BrXX(&TSGN)],
+    t4: &[Action::BrX0(&RC), Action::BrX1(&RB), Action::BrXX(&TMZ)],
+    t5: &[Action::Br1X(&RL), Action::Br1X(&TSGN)],
+    t6: &[Action::Br1X(&WA), Action::Br11(&R1C), Action::Br11(&RB1)],
+    t7: &[Action::BrXX(&RL), Action::BrXX(&WB), Action::BrXX(&TMZ)],
+    t8: &[Action::Br0X(&RC), Action::Br0X(&TMZ)],
+    t9: &[Action::Br1X(&WL), Action::Br11(&R1C), Action::Br11(&RB1)],
+    t10: &[Action::BrXX(&RA), Action::BrXX(&WB), Action::BrXX(&TSGN2)],
+    t11: &[Action::BrX0(&RB), Action::BrX1(&RC), Action::BrXX(&WA)],
+    t12: &[Action::BrXX(&RL), Action::BrXX(&WB), Action::BrXX(&DVST)],
+};
+
+pub static DV1: Subinstruction = Subinstruction {
+    name: "DV1",
+    t1: &[
+        Action::BrX0(&RB),
+        Action::BrX1(&RC),
+        Action::BrXX(&WL),
+        Action::BrXX(&TSGN),
+    ],
+    t2: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t3: &[Action::BrX1(&RC), Action::BrX1(&WY), Action::BrX1(&PONEX)],
+    t4: &[Action::BrX1(&RU), Action::BrX1(&WB)],
+    t5: &[Action::BrX1(&RC), Action::BrX1(&WA)],
+    t6: &[Action::BrX1(&RL), Action::BrX1(&WY), Action::BrX1(&PONEX)],
+    t7: &[Action::BrX1(&RU), Action::BrX1(&WL)],
+    t8: &[Action::BrXX(&RG), Action::BrXX(&WB), Action::BrXX(&TSGN)],
+    t9: &[Action::BrX0(&RB), Action::BrX1(&RC), Action::BrXX(&WY)],
+    t10: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t11: &[Action::BrXX(&RC), Action::BrXX(&WB)],
+    t12: &[Action::BrXX(&RU), Action::BrXX(&WA), Action::BrXX(&DVST)],
+};
+
+pub static DV3: Subinstruction = Subinstruction {
+    name: "DV3",
+    t1: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t2: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t3: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t4: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t5: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t6: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t7: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t8: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t9: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t10: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t11: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t12: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+        Action::BrXX(&DVST),
+    ],
+};
+
+pub static DV7: Subinstruction = Subinstruction {
+    name: "DV7",
+    t1: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t2: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t3: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t4: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t5: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t6: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t7: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t8: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t9: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t10: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t11: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t12: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+        Action::BrXX(&DVST),
+    ],
+};
+
+pub static DV6: Subinstruction = Subinstruction {
+    name: "DV6",
+    t1: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t2: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t3: &[
+        Action::BrXX(&RB),
+        Action::BrXX(&WYD),
+        Action::BrXX(&A2X),
+        Action::BrXX(&DV_DIVIDEND_BIT),
+        Action::BrXX(&TSGU),
+        Action::BrXX(&DV_CLEAR_X),
+    ],
+    t4: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WB),
+        Action::BrXX(&DV_QUOTIENT_BIT),
+    ],
+    t5: &[Action::BrXX(&RC), Action::BrXX(&WY)],
+    t6: &[Action::BrXX(&RL), Action::BrXX(&WB)],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&TSGN)],
+    t8: &[
+        Action::Br00(&RB),
+        Action::Br10(&RC),
+        Action::Br01(&RC),
+        Action::Br11(&RB),
+        Action::BrXX(&WA),
+        Action::BrXX(&DVST),
+    ],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static DV4: Subinstruction = Subinstruction {
+    name: "DV4",
+    t1: &[Action::BrXX(&RU), Action::BrXX(&WB)],
+    t2: &[Action::Br0X(&RB), Action::Br1X(&RC), Action::BrXX(&WL)],
+    t3: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static DXCH0: Subinstruction = Subinstruction {
+    name: "DXCH0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RL10BB), Action::BrXX(&WB)],
+    t4: &[Action::BrXX(&RC), Action::BrXX(&WY), Action::BrXX(&PONEX)],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t6: &[Action::BrXX(&RL), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t7: &[Action::BrXX(&RB), Action::BrXX(&WL)],
+    t8: &[Action::BrXX(&RU), Action::BrXX(&WB)],
+    t9: &[Action::BrXX(&RC), Action::BrXX(&WS), Action::BrXX(&ST1)],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static DXCH1: Subinstruction = Subinstruction {
+    name: "DXCH1",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WA)],
+    t6: &[],
+    t7: &[Action::BrXX(&RB), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// EDRUPT fetches the word at K, which S still holds, to B and increments
+// Z, like STD2 without NISQ. It then saves Z in ZRUPT and B in BRUPT like
+// RUPT0 and RUPT1, even if interrupts are not allowed. Z is cleared instead
+// of being read from RRPA, so that STD2 transfers control to address 0. No
+// interrupt request is being serviced, hence KRPT is not generated.
+pub static EDRUPT0: Subinstruction = Subinstruction {
+    name: "EDRUPT0",
+    t1: &[Action::BrXX(&RZ), Action::BrXX(&WY12), Action::BrXX(&CI)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[Action::BrXX(&RU), Action::BrXX(&WZ)],
+    t7: &[],
+    t8: &[Action::BrXX(&RAD), Action::BrXX(&WB)],
+    t9: &[],
+    t10: &[],
+    t11: &[Action::BrXX(&ST1)],
+    t12: &[],
+};
+
+pub static EDRUPT1: Subinstruction = Subinstruction {
+    name: "EDRUPT1",
+    t1: &[Action::BrXX(&R15), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[],
+    t9: &[Action::BrXX(&RZ), Action::BrXX(&WG)],
+    t10: &[],
+    t11: &[Action::BrXX(&ST1), Action::BrXX(&ST2)],
+    t12: &[],
+};
+
+pub static EDRUPT3: Subinstruction = Subinstruction {
+    name: "EDRUPT3",
+    t1: &[Action::BrXX(&R15), Action::BrXX(&RB2), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&WZ)],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[],
+    t11: &[Action::BrXX(&ST2)],
+    t12: &[],
+};
+
+pub static GOJ1: Subinstruction = Subinstruction {
+    name: "GOJ1",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[Action::BrXX(&RSTRT), Action::BrXX(&WS), Action::BrXX(&WB)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static INCR0: Subinstruction = Subinstruction {
+    name: "INCR0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[
+        Action::BrXX(&RG),
+        Action::BrXX(&WY),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+        Action::BrXX(&TPZG),
+    ],
+    t6: &[Action::BrXX(&PONEX)],
+    t7: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WSC),
+        Action::BrXX(&WG),
+        Action::BrXX(&WOVR),
+    ],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static LXCH0: Subinstruction = Subinstruction {
+    name: "LXCH0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RL), Action::BrXX(&WB)],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WL)],
+    t6: &[],
+    t7: &[Action::BrXX(&RB), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static MASK0: Subinstruction = Subinstruction {
+    name: "MASK0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t5: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[Action::BrXX(&RC), Action::BrXX(&RA), Action::BrXX(&WB)],
+    t11: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t12: &[],
+};
+
+// MP multiplies the magnitudes of A and K, one bit of the multiplier at a
+// time. The multiplier is shifted out of L while the low bits of the
+// product are shifted in. BR1 and BR2 hold the signs of the operands, and
+// both words of the product are complemented at the end if they differ,
+// including when the product is zero.
+//
+// This is not the sequence of the hardware, which handles two bits of the
+// multiplier at each ZIP and moves them with L2GD and TL15. Here, each of
+// the 14 MP_ADD/MP_SHIFT pairs spread over MP0, MP1 and MP3 handles a
+// single bit, with pulses which do not exist in the hardware (see
+// `emulated_pulses`). Only the product left in A and L at the end of MP3
+// matches the hardware, the registers in between do not.
+pub static MP0: Subinstruction = Subinstruction {
+    name: "MP0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RA), Action::BrXX(&WB), Action::BrXX(&TSGN)],
+    t4: &[Action::BrX0(&RB), Action::BrX1(&RC), Action::BrXX(&WL)],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB), Action::BrXX(&TSGN2)],
+    t8: &[Action::Br1X(&RC), Action::Br1X(&WB)],
+    t9: &[Action::BrXX(&WA)],
+    t10: &[],
+    t11: &[Action::BrXX(&MP_ADD)],
+    t12: &[
+        Action::BrXX(&MP_SHIFT),
+        Action::BrXX(&MP_ADD),
+        Action::BrXX(&ST1),
+    ],
+};
+
+pub static MP1: Subinstruction = Subinstruction {
+    name: "MP1",
+    t1: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t2: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t3: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t4: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t5: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t6: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t7: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t8: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t9: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t10: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t11: &[Action::BrXX(&MP_SHIFT), Action::BrXX(&MP_ADD)],
+    t12: &[
+        Action::BrXX(&MP_SHIFT),
+        Action::BrXX(&MP_ADD),
+        Action::BrXX(&ST1),
+        Action::BrXX(&ST2),
+    ],
+};
+
+pub static MP3: Subinstruction = Subinstruction {
+    name: "MP3",
+    t1: &[Action::BrXX(&MP_SHIFT)],
+    t2: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t3: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t4: &[
+        Action::Br00(&RB),
+        Action::Br10(&RC),
+        Action::Br01(&RC),
+        Action::Br11(&RB),
+        Action::BrXX(&WA),
+    ],
+    t5: &[Action::BrXX(&RL), Action::BrXX(&WB)],
+    t6: &[
+        Action::Br00(&RB),
+        Action::Br10(&RC),
+        Action::Br01(&RC),
+        Action::Br11(&RB),
+        Action::BrXX(&WL),
+    ],
+    t7: &[],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// MSU subtracts the 15-bit words as two's complement numbers, then
+// converts a negative difference back to one's complement.
+pub static MSU0: Subinstruction = Subinstruction {
+    name: "MSU0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t6: &[
+        Action::BrXX(&RC),
+        Action::BrXX(&WY),
+        Action::BrXX(&CI),
+        Action::BrXX(&A2X),
+        Action::BrXX(&NEAC),
+    ],
+    t7: &[Action::BrXX(&RUS), Action::BrXX(&WA), Action::BrXX(&TSGN)],
     t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
-    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
-    t10: &[Action::BrXX(&RB), Action::BrXX(&WA)],
-    t11: &[],
+    t9: &[],
+    t10: &[Action::BrX1(&RA), Action::BrX1(&WY), Action::BrX1(&MONEX)],
+    t11: &[Action::BrX1(&RUS), Action::BrX1(&WA)],
     t12: &[],
 };
 
-pub static CS0: Subinstruction = Subinstruction {
-    name: "CS0",
+pub static NDX0: Subinstruction = Subinstruction {
+    name: "NDX0",
     t1: &[],
-    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG), Action::BrXX(&TRSM)],
     t3: &[],
     t4: &[],
     t5: &[],
     t6: &[],
     t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
-    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST1)],
     t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
-    t10: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t10: &[],
     t11: &[],
     t12: &[],
 };
 
-pub static GOJ1: Subinstruction = Subinstruction {
-    name: "GOJ1",
-    t1: &[],
+pub static NDX1: Subinstruction = Subinstruction {
+    name: "NDX1",
+    t1: &[Action::BrXX(&RZ), Action::BrXX(&WY12), Action::BrXX(&CI)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG), Action::BrXX(&NISQ)],
+    t3: &[Action::BrXX(&RU), Action::BrXX(&WZ)],
+    t4: &[Action::BrXX(&RA), Action::BrXX(&WY)],
+    t5: &[Action::BrXX(&RB), Action::BrXX(&WA)],
+    t6: &[Action::BrXX(&RU), Action::BrXX(&WB)],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WY)],
+    t8: &[Action::BrXX(&A2X)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WA)],
+    t10: &[Action::BrXX(&RU), Action::BrXX(&WB), Action::BrXX(&WS)],
+    t11: &[],
+    t12: &[],
+};
+
+pub static QXCH0: Subinstruction = Subinstruction {
+    name: "QXCH0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
     t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RQ), Action::BrXX(&WB)],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WQ)],
+    t6: &[],
+    t7: &[Action::BrXX(&RB), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// RESUME is INDEX 17: NDX0 loads BRUPT into B, then RSM3 restores Z from
+// ZRUPT and executes the instruction held in B.
+pub static RSM3: Subinstruction = Subinstruction {
+    name: "RSM3",
+    t1: &[Action::BrXX(&R15), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG), Action::BrXX(&NISQ)],
     t3: &[],
     t4: &[],
-    t5: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WZ)],
     t6: &[],
     t7: &[],
-    t8: &[Action::BrXX(&RSTRT), Action::BrXX(&WS), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RB), Action::BrXX(&WS)],
     t9: &[],
     t10: &[],
     t11: &[],
     t12: &[],
 };
 
-pub static INCR0: Subinstruction = Subinstruction {
-    name: "INCR0",
-    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+// RUPT saves Z in ZRUPT (15) and B, the next instruction, in BRUPT (17),
+// then transfers control to the service routine of the interrupt, like STD2.
+pub static RUPT0: Subinstruction = Subinstruction {
+    name: "RUPT0",
+    t1: &[Action::BrXX(&R15), Action::BrXX(&WS)],
     t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
     t3: &[],
     t4: &[],
-    t5: &[
-        Action::BrXX(&RG),
-        Action::BrXX(&WY),
-        Action::BrXX(&TSGN),
-        Action::BrXX(&TMZ),
-        Action::BrXX(&TPZG),
-    ],
-    t6: &[Action::BrXX(&PONEX)],
-    t7: &[
-        Action::BrXX(&RU),
-        Action::BrXX(&WSC),
-        Action::BrXX(&WG),
-        Action::BrXX(&WOVR),
-    ],
-    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[],
+    t9: &[Action::BrXX(&RZ), Action::BrXX(&WG)],
+    t10: &[],
+    t11: &[Action::BrXX(&ST1)],
+    t12: &[],
+};
+
+pub static RUPT1: Subinstruction = Subinstruction {
+    name: "RUPT1",
+    t1: &[Action::BrXX(&R15), Action::BrXX(&RB2), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[Action::BrXX(&RRPA), Action::BrXX(&WZ), Action::BrXX(&KRPT)],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[],
+    t11: &[Action::BrXX(&ST1), Action::BrXX(&ST2)],
+    t12: &[],
+};
+
+pub static RUPT3: Subinstruction = Subinstruction {
+    name: "RUPT3",
+    t1: &[Action::BrXX(&RZ), Action::BrXX(&WY12), Action::BrXX(&CI)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG), Action::BrXX(&NISQ)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[Action::BrXX(&RU), Action::BrXX(&WZ)],
+    t7: &[],
+    t8: &[Action::BrXX(&RAD), Action::BrXX(&WB), Action::BrXX(&WS)],
     t9: &[],
     t10: &[],
     t11: &[],
@@ -173,6 +1139,22 @@ pub static STD2: Subinstruction = Subinstruction {
     t12: &[],
 };
 
+pub static SU0: Subinstruction = Subinstruction {
+    name: "SU0",
+    t1: &[],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RB), Action::BrXX(&WG)],
+    t10: &[Action::BrXX(&RC), Action::BrXX(&WY), Action::BrXX(&A2X)],
+    t11: &[Action::BrXX(&RU), Action::BrXX(&WA)],
+    t12: &[],
+};
+
 pub static TC0: Subinstruction = Subinstruction {
     name: "TC0",
     t1: &[Action::BrXX(&RB), Action::BrXX(&WY12), Action::BrXX(&CI)],
@@ -247,6 +1229,157 @@ pub static XCH0: Subinstruction = Subinstruction {
     t12: &[],
 };
 
+// Counter subinstructions are inserted between two MCTs to service a
+// counter request. They do not change the stage counter.
+pub static PINC: Subinstruction = Subinstruction {
+    name: "PINC",
+    t1: &[Action::BrXX(&RSCT), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[
+        Action::BrXX(&RG),
+        Action::BrXX(&WY),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+        Action::BrXX(&TPZG),
+    ],
+    t6: &[Action::BrXX(&PONEX)],
+    t7: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WSC),
+        Action::BrXX(&WG),
+        Action::BrXX(&WOVR),
+    ],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static MINC: Subinstruction = Subinstruction {
+    name: "MINC",
+    t1: &[Action::BrXX(&RSCT), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[
+        Action::BrXX(&RG),
+        Action::BrXX(&WY),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+        Action::BrXX(&TPZG),
+    ],
+    t6: &[Action::BrXX(&MONEX)],
+    t7: &[
+        Action::BrXX(&RU),
+        Action::BrXX(&WSC),
+        Action::BrXX(&WG),
+        Action::BrXX(&WOVR),
+    ],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// DINC diminishes both +1 and -1 to -0. Only a zero counter generates
+// ZOUT.
+pub static DINC: Subinstruction = Subinstruction {
+    name: "DINC",
+    t1: &[Action::BrXX(&RSCT), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[
+        Action::BrXX(&RG),
+        Action::BrXX(&WY),
+        Action::BrXX(&TSGN),
+        Action::BrXX(&TMZ),
+        Action::BrXX(&TPZG),
+    ],
+    t6: &[
+        Action::Br00(&MONEX),
+        Action::Br00(&POUT),
+        Action::Br01(&PONEX),
+        Action::Br01(&MOUT),
+        Action::Br1X(&ZOUT),
+    ],
+    t7: &[Action::BrXX(&RU), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// PCDU and MCDU count in two's complement, as used by the CDU counters.
+pub static PCDU: Subinstruction = Subinstruction {
+    name: "PCDU",
+    t1: &[Action::BrXX(&RSCT), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WY)],
+    t6: &[Action::BrXX(&PONEX), Action::BrXX(&NEAC)],
+    t7: &[Action::BrXX(&RUS), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static MCDU: Subinstruction = Subinstruction {
+    name: "MCDU",
+    t1: &[Action::BrXX(&RSCT), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WY)],
+    t6: &[Action::BrXX(&MONEX), Action::BrXX(&CI), Action::BrXX(&NEAC)],
+    t7: &[Action::BrXX(&RUS), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static SHINC: Subinstruction = Subinstruction {
+    name: "SHINC",
+    t1: &[Action::BrXX(&RSCT), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WYD)],
+    t6: &[],
+    t7: &[Action::BrXX(&RUS), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static SHANC: Subinstruction = Subinstruction {
+    name: "SHANC",
+    t1: &[Action::BrXX(&RSCT), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RSC), Action::BrXX(&WG)],
+    t3: &[],
+    t4: &[],
+    t5: &[Action::BrXX(&RG), Action::BrXX(&WYD)],
+    t6: &[Action::BrXX(&CI)],
+    t7: &[Action::BrXX(&RUS), Action::BrXX(&WSC), Action::BrXX(&WG)],
+    t8: &[],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
 pub static WRITE0: Subinstruction = Subinstruction {
     name: "WRITE0",
     t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
@@ -262,3 +1395,105 @@ pub static WRITE0: Subinstruction = Subinstruction {
     t11: &[],
     t12: &[],
 };
+
+pub static READ0: Subinstruction = Subinstruction {
+    name: "READ0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RCH), Action::BrXX(&WB)],
+    t3: &[Action::BrXX(&RB), Action::BrXX(&WA)],
+    t4: &[],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// RAND and WAND compute the logical product as the complement of the
+// logical sum of the complements, like MASK.
+pub static RAND0: Subinstruction = Subinstruction {
+    name: "RAND0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t3: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t4: &[Action::BrXX(&RCH), Action::BrXX(&WB)],
+    t5: &[Action::BrXX(&RC), Action::BrXX(&RA), Action::BrXX(&WB)],
+    t6: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t7: &[],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static WAND0: Subinstruction = Subinstruction {
+    name: "WAND0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t3: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t4: &[Action::BrXX(&RCH), Action::BrXX(&WB)],
+    t5: &[Action::BrXX(&RC), Action::BrXX(&RA), Action::BrXX(&WB)],
+    t6: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t7: &[Action::BrXX(&RA), Action::BrXX(&WCH)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static ROR0: Subinstruction = Subinstruction {
+    name: "ROR0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t3: &[],
+    t4: &[Action::BrXX(&RCH), Action::BrXX(&RB), Action::BrXX(&WA)],
+    t5: &[],
+    t6: &[],
+    t7: &[],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+pub static WOR0: Subinstruction = Subinstruction {
+    name: "WOR0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t3: &[],
+    t4: &[Action::BrXX(&RCH), Action::BrXX(&RB), Action::BrXX(&WA)],
+    t5: &[Action::BrXX(&RA), Action::BrXX(&WCH)],
+    t6: &[],
+    t7: &[],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[],
+    t10: &[],
+    t11: &[],
+    t12: &[],
+};
+
+// RXOR computes the exclusive or as (A AND NOT C) OR (NOT A AND C), where
+// C is the channel. Both terms are obtained as complements of logical sums.
+// The channel is read once and kept in Y, and the intermediate sum is kept
+// in G with WGN, since S holds the channel address and WG would edit it.
+pub static RXOR0: Subinstruction = Subinstruction {
+    name: "RXOR0",
+    t1: &[Action::BrXX(&RL10BB), Action::BrXX(&WS)],
+    t2: &[Action::BrXX(&RA), Action::BrXX(&WB)],
+    t3: &[Action::BrXX(&RCH), Action::BrXX(&WY)],
+    t4: &[Action::BrXX(&RC), Action::BrXX(&RU), Action::BrXX(&WGN)],
+    t5: &[Action::BrXX(&RU), Action::BrXX(&WB)],
+    t6: &[Action::BrXX(&RC), Action::BrXX(&RA), Action::BrXX(&WB)],
+    t7: &[Action::BrXX(&RC), Action::BrXX(&WA)],
+    t8: &[Action::BrXX(&RZ), Action::BrXX(&WS), Action::BrXX(&ST2)],
+    t9: &[Action::BrXX(&RG), Action::BrXX(&WB)],
+    t10: &[Action::BrXX(&RC), Action::BrXX(&RA), Action::BrXX(&WA)],
+    t11: &[],
+    t12: &[],
+};
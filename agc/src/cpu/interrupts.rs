@@ -0,0 +1,123 @@
+use crate::word::W12;
+
+/// Erasable locations where the interrupt service routines save the
+/// central registers. RUPT itself saves ZRUPT and BRUPT.
+///
+/// These are ordinary erasable locations, read and written by the memory
+/// cycle. Only addresses 0 through 7 are central registers handled by RSC
+/// and WSC.
+pub const ARUPT: u16 = 0o10;
+pub const LRUPT: u16 = 0o11;
+pub const QRUPT: u16 = 0o12;
+pub const ZRUPT: u16 = 0o15;
+pub const BBRUPT: u16 = 0o16;
+pub const BRUPT: u16 = 0o17;
+
+/// Interrupt sources, in order of decreasing priority
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Interrupt {
+    /// T6RUPT: TIME6 decremented to zero
+    T6,
+    /// T5RUPT: TIME5 overflow
+    T5,
+    /// T3RUPT: TIME3 overflow
+    T3,
+    /// T4RUPT: TIME4 overflow
+    T4,
+    /// KEYRUPT1: keystroke on the main DSKY
+    Key1,
+    /// KEYRUPT2: keystroke on the navigation DSKY
+    Key2,
+    /// UPRUPT: uplink word received
+    Uprupt,
+    /// DOWNRUPT: downlink telemetry word sent
+    Downrupt,
+    /// RADARUPT: radar measurement available
+    Radar,
+    /// HANDRUPT: hand controller or trap
+    Handrupt,
+}
+
+impl Interrupt {
+    /// All interrupt sources, in order of decreasing priority
+    pub const ALL: [Interrupt; 10] = [
+        Interrupt::T6,
+        Interrupt::T5,
+        Interrupt::T3,
+        Interrupt::T4,
+        Interrupt::Key1,
+        Interrupt::Key2,
+        Interrupt::Uprupt,
+        Interrupt::Downrupt,
+        Interrupt::Radar,
+        Interrupt::Handrupt,
+    ];
+
+    /// Priority number of the interrupt, starting at 1 for T6RUPT
+    pub fn number(self) -> u16 {
+        self as u16 + 1
+    }
+
+    /// Address of the interrupt service routine, 4000 + 4n
+    pub fn vector(self) -> W12 {
+        W12::from(0o4000 + 4 * self.number())
+    }
+}
+
+/// Pending interrupt requests
+#[derive(Copy, Clone, Default)]
+pub struct InterruptRequests {
+    pending: [bool; 10],
+}
+
+impl InterruptRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.pending[interrupt as usize] = true;
+    }
+
+    pub fn clear(&mut self, interrupt: Interrupt) {
+        self.pending[interrupt as usize] = false;
+    }
+
+    pub fn is_pending(&self, interrupt: Interrupt) -> bool {
+        self.pending[interrupt as usize]
+    }
+
+    /// Returns the pending interrupt with the highest priority
+    pub fn highest(&self) -> Option<Interrupt> {
+        Interrupt::ALL
+            .iter()
+            .copied()
+            .find(|interrupt| self.is_pending(*interrupt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupt_vector() {
+        assert_eq!(Interrupt::T6.vector(), W12::from(0o4004));
+        assert_eq!(Interrupt::Key1.vector(), W12::from(0o4024));
+        assert_eq!(Interrupt::Handrupt.vector(), W12::from(0o4050));
+    }
+
+    #[test]
+    fn interrupt_priority() {
+        let mut requests = InterruptRequests::new();
+        assert_eq!(requests.highest(), None);
+
+        requests.request(Interrupt::Radar);
+        requests.request(Interrupt::T4);
+        assert_eq!(requests.highest(), Some(Interrupt::T4));
+
+        requests.clear(Interrupt::T4);
+        assert_eq!(requests.highest(), Some(Interrupt::Radar));
+        assert!(!requests.is_pending(Interrupt::T4));
+    }
+}
@@ -1,13 +1,47 @@
 use registers::BranchRegister;
+use std::ops::RangeInclusive;
 
+use crate::cpu::control_pulses::{ControlPulse, RB, RG, WB, WG, WSC};
 use crate::cpu::instructions::*;
 use crate::cpu::registers::{AddressRegister, MemoryAddress, SequenceRegister};
-use crate::memory::{ErasableStorage, FixedStorage, MemoryWord};
+use crate::io::{Channels, Peripheral, Requests};
+use crate::memory::{ErasableStorage, FixedStorage, MemoryWord, FIXED_NUM_BANKS};
 use crate::word::*;
 
+mod adder;
+mod alarms;
 mod control_pulses;
+pub mod counters;
+mod emulated_pulses;
+mod error;
 mod instructions;
+pub mod interrupts;
 mod registers;
+mod scaler;
+mod snapshot;
+
+pub use alarms::Alarm;
+use alarms::{Watchdogs, NEWJOB, RESTART_CHANNEL};
+use counters::{CounterCell, CounterContext, CounterInstruction, CounterRequests};
+pub use error::EmulationError;
+pub(crate) use instructions::{is_control_pulse_name, is_subinstruction_name};
+pub use interrupts::Interrupt;
+use interrupts::InterruptRequests;
+use scaler::Scaler;
+pub use snapshot::{CpuSnapshot, SnapshotError, SNAPSHOT_VERSION};
+
+/// Output channels cleared by GOJAM
+const RESET_CHANNELS: [u16; 10] = [0o5, 0o6, 0o7, 0o10, 0o11, 0o12, 0o13, 0o14, 0o34, 0o35];
+
+/// Input channel of the PRO key, whose inputs are active-low
+const PRO_CHANNEL: u16 = 0o32;
+
+/// Channels at power-on, with the active-low inputs of channel 32 released
+fn initial_channels() -> Channels {
+    let mut channels = Channels::new();
+    channels.write(W9::from(PRO_CHANNEL), W15::from(0o77777));
+    channels
+}
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum TimePulse {
@@ -63,6 +97,15 @@ impl From<TimePulse> for usize {
     }
 }
 
+/// Access to erasable memory or to a channel, made by a control pulse
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MemoryAccess {
+    ErasableRead(W3, W8),
+    ErasableWrite(W3, W8),
+    ChannelRead(W9),
+    ChannelWrite(W9),
+}
+
 pub struct Cpu {
     // These registers are visible to the programmer
     /// Accumulator
@@ -93,6 +136,8 @@ pub struct Cpu {
     pub y: W16,
     /// Carry flip-flop
     pub ci: bool,
+    /// End-around carry inhibit flip-flop, set by NEAC and cleared by WY
+    neac: bool,
     /// Branch register (used to make decision)
     pub br: BranchRegister,
 
@@ -101,9 +146,24 @@ pub struct Cpu {
     erasable_storage: ErasableStorage,
     /// Fixed (read-only) memory storage
     fixed_storage: FixedStorage,
+    /// Hash of the fixed storage, identifying it in snapshots
+    fixed_storage_hash: u64,
+    /// I/O channels
+    channels: Channels,
+    /// Peripherals attached to ranges of channels
+    peripherals: Vec<(RangeInclusive<W9>, Box<dyn Peripheral>)>,
 
     // Emulation parameters
     pub current_timepulse: TimePulse,
+    /// Memory parity is checked on every read
+    ///
+    /// This mirrors the test switch which disables the parity alarm.
+    parity_check: bool,
+    /// The Night Watchman, TC Trap and Rupt Lock alarms are enabled
+    restart_alarms: bool,
+
+    /// Erasable memory and channel accesses of the last control pulse
+    accesses: Vec<MemoryAccess>,
 
     /// Value of S after T1
     ///
@@ -111,6 +171,13 @@ pub struct Cpu {
     /// to the S register, the original address is used when writing
     /// back to erasable memory
     current_s: AddressRegister,
+    /// B holds a copy of the word read from memory
+    b_holds_g: bool,
+    /// G was written after the memory read, so that a new word is written
+    /// back to erasable memory at T10
+    ///
+    /// Writing back a copy of the word read, held in B, only restores it.
+    g_written: bool,
     /// At next T12, read the next instruction into register SQ
     ///
     /// This is generated by control pulse NISQ.
@@ -121,16 +188,41 @@ pub struct Cpu {
     ext: bool,
     /// Value of ST at next MCT
     next_st: W3,
+    /// Interrupts are inhibited by INHINT until RELINT
     inhibit_interrupts: bool,
+    /// Pending interrupt requests
+    interrupts: InterruptRequests,
+    /// The RUPT pseudo-instruction is being executed instead of SQ
+    rupt: bool,
+    /// Interrupt vectored to by RRPA, whose request is reset by KRPT
+    vectored_interrupt: Option<Interrupt>,
+    /// An interrupt service routine is in progress
+    ///
+    /// No other interrupt is taken until RESUME.
+    interrupt_in_progress: bool,
+    /// Pending counter requests
+    counters: CounterRequests,
+    /// Counter subinstruction being executed and its counter cell
+    counter: Option<(CounterCell, CounterInstruction)>,
+    /// Registers saved while a counter subinstruction is executed
+    counter_context: Option<CounterContext>,
+    /// Scaler driving the timers
+    scaler: Scaler,
+    /// Alarm which caused the last restart, latched until channel 77 is written
+    alarm: Option<Alarm>,
+    /// Night Watchman, TC Trap and Rupt Lock
+    watchdogs: Watchdogs,
+    /// The computer is in standby: only the scaler, TIME1 and TIME2 run
+    standby: bool,
 }
 
 impl Cpu {
     /// Create a CPU from a fixed storage ROM
     ///
     /// All internal parameters will be initialized to zero and
-    /// the CPU will be reset, ready to perform a GOJAM.
+    /// the CPU will be powered on, ready to execute GOJ1.
     pub fn new(fixed_storage: FixedStorage) -> Self {
-        Cpu {
+        let mut cpu = Cpu {
             a: W16::zero(),
             l: W16::zero(),
             q: W16::zero(),
@@ -146,81 +238,211 @@ impl Cpu {
             x: W16::zero(),
             y: W16::zero(),
             ci: false,
+            neac: false,
             br: BranchRegister::new(),
 
             erasable_storage: ErasableStorage::new(),
+            fixed_storage_hash: fixed_storage.hash(),
             fixed_storage,
+            channels: initial_channels(),
+            peripherals: Vec::new(),
 
             current_timepulse: TimePulse::T1,
+            parity_check: true,
+            restart_alarms: false,
+            accesses: Vec::new(),
             current_s: AddressRegister::zero(),
+            b_holds_g: false,
+            g_written: false,
             nisq: false,
             ext: false,
             next_st: W3::zero(),
             inhibit_interrupts: false,
-        }
+            interrupts: InterruptRequests::new(),
+            rupt: false,
+            vectored_interrupt: None,
+            interrupt_in_progress: false,
+            counters: CounterRequests::new(),
+            counter: None,
+            counter_context: None,
+            scaler: Scaler::new(),
+            alarm: None,
+            watchdogs: Watchdogs::new(),
+            standby: false,
+        };
+        cpu.power_on();
+        cpu
     }
 
     pub fn current_subinstruction(&self) -> &'static Subinstruction {
+        self.try_current_subinstruction()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Subinstruction executed in the current MCT
+    ///
+    /// Returns an error if no subinstruction exists for SQ and ST.
+    pub fn try_current_subinstruction(&self) -> Result<&'static Subinstruction, EmulationError> {
+        // Counter subinstructions are inserted between MCTs
+        if let Some((_, instruction)) = self.counter {
+            return Ok(match instruction {
+                CounterInstruction::Pinc => &PINC,
+                CounterInstruction::Minc => &MINC,
+                CounterInstruction::Dinc => &DINC,
+                CounterInstruction::Pcdu => &PCDU,
+                CounterInstruction::Mcdu => &MCDU,
+                CounterInstruction::Shinc => &SHINC,
+                CounterInstruction::Shanc => &SHANC,
+            });
+        }
+
         // STD2 is always executed if ST = 0b010
         if self.st == W3::from(0b010) {
-            return &STD2;
+            return Ok(&STD2);
+        }
+
+        // RUPT is executed in place of the instruction held in B
+        if self.rupt {
+            // RRPA vectors to the highest priority pending interrupt
+            if self.st == W3::from(0b001)
+                && self.current_timepulse <= TimePulse::T3
+                && self.interrupts.highest().is_none()
+            {
+                return Err(EmulationError::NoPendingInterrupt { z: self.z });
+            }
+
+            return match self.st.as_u16() {
+                0b000 => Ok(&RUPT0),
+                0b001 => Ok(&RUPT1),
+                0b011 => Ok(&RUPT3),
+                _ => Err(EmulationError::IllegalStage {
+                    st: self.st,
+                    z: self.z,
+                }),
+            };
         }
 
+        self.decode_sq().ok_or(EmulationError::UnimplementedOpcode {
+            sq: self.sq.inner(),
+            st: self.st,
+            z: self.z,
+        })
+    }
+
+    /// Subinstruction of the instruction held in SQ at the current stage
+    fn decode_sq(&self) -> Option<&'static Subinstruction> {
         if !self.sq.is_extended() {
             // Non-extended subinstructions
             match self.sq.order_code().as_u16() {
                 0b000 => match self.st.as_u16() {
-                    0b000 => &TC0,
-                    0b001 => &GOJ1,
-                    _ => panic!("opcode {} with st {} does not exist", self.sq, self.st),
+                    0b000 => Some(&TC0),
+                    0b001 => Some(&GOJ1),
+                    _ => None,
                 },
                 0b001 => match self.sq.quarter_code().as_u16() {
-                    0b00 => unimplemented!("opcode {}", self.sq),
-                    0b01 | 0b10 | 0b11 => &TCF0,
-                    _ => panic!("opcode {} with st {} does not exist", self.sq, self.st),
+                    0b00 => Some(&CCS0),
+                    0b01..=0b11 => Some(&TCF0),
+                    _ => None,
                 },
                 0b010 => match self.sq.quarter_code().as_u16() {
-                    0b00 => unimplemented!("opcode {}", self.sq),
-                    0b01 => unimplemented!("opcode {}", self.sq),
-                    0b10 => &INCR0,
-                    0b11 => unimplemented!("opcode {}", self.sq),
-                    _ => panic!("opcode {} with st {} does not exist", self.sq, self.st),
+                    0b00 => match self.st.as_u16() {
+                        0b000 => Some(&DAS0),
+                        0b001 => Some(&DAS1),
+                        _ => None,
+                    },
+                    0b01 => Some(&LXCH0),
+                    0b10 => Some(&INCR0),
+                    0b11 => Some(&ADS0),
+                    _ => None,
                 },
-                0b011 => &CA0,
-                0b100 => &CS0,
+                0b011 => Some(&CA0),
+                0b100 => Some(&CS0),
                 0b101 => match self.sq.quarter_code().as_u16() {
-                    0b00 => unimplemented!("opcode {}", self.sq),
-                    0b01 => unimplemented!("opcode {}", self.sq),
-                    0b10 => &TS0,
-                    0b11 => &XCH0,
-                    _ => unimplemented!("opcode {}", self.sq),
+                    0b00 => match self.st.as_u16() {
+                        0b000 => Some(&NDX0),
+                        0b001 => Some(&NDX1),
+                        0b011 => Some(&RSM3),
+                        _ => None,
+                    },
+                    0b01 => match self.st.as_u16() {
+                        0b000 => Some(&DXCH0),
+                        0b001 => Some(&DXCH1),
+                        _ => None,
+                    },
+                    0b10 => Some(&TS0),
+                    0b11 => Some(&XCH0),
+                    _ => None,
                 },
-                0b110 => unimplemented!("opcode {}", self.sq),
-                0b111 => unimplemented!("opcode {}", self.sq),
-                _ => panic!("opcode {} does not exist", self.sq),
+                0b110 => Some(&AD0),
+                0b111 => Some(&MASK0),
+                _ => None,
             }
         } else {
             // Extended subinstructions
             match self.sq.order_code().as_u16() {
                 0b000 => match self.sq.peripheral_code().as_u16() {
-                    0b000 => unimplemented!("opcode {}", self.sq),
-                    0b001 => &WRITE0,
-                    0b010 => unimplemented!("opcode {}", self.sq),
-                    0b011 => unimplemented!("opcode {}", self.sq),
-                    0b100 => unimplemented!("opcode {}", self.sq),
-                    0b101 => unimplemented!("opcode {}", self.sq),
-                    0b110 => unimplemented!("opcode {}", self.sq),
-                    0b111 => unimplemented!("opcode {}", self.sq),
-                    _ => panic!("opcode {} does not exist", self.sq),
-                }
-                0b001 => unimplemented!("opcode {}", self.sq),
-                0b010 => unimplemented!("opcode {}", self.sq),
-                0b011 => unimplemented!("opcode {}", self.sq),
-                0b100 => unimplemented!("opcode {}", self.sq),
-                0b101 => unimplemented!("opcode {}", self.sq),
-                0b110 => unimplemented!("opcode {}", self.sq),
-                0b111 => unimplemented!("opcode {}", self.sq),
-                _ => panic!("opcode {} does not exist", self.sq),
+                    0b000 => Some(&READ0),
+                    0b001 => Some(&WRITE0),
+                    0b010 => Some(&RAND0),
+                    0b011 => Some(&WAND0),
+                    0b100 => Some(&ROR0),
+                    0b101 => Some(&WOR0),
+                    0b110 => Some(&RXOR0),
+                    0b111 => match self.st.as_u16() {
+                        0b000 => Some(&EDRUPT0),
+                        0b001 => Some(&EDRUPT1),
+                        0b011 => Some(&EDRUPT3),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                0b001 => match self.sq.quarter_code().as_u16() {
+                    0b00 => match self.st.as_u16() {
+                        0b000 => Some(&DV0),
+                        0b001 => Some(&DV1),
+                        0b011 => Some(&DV3),
+                        0b111 => Some(&DV7),
+                        0b110 => Some(&DV6),
+                        0b100 => Some(&DV4),
+                        _ => None,
+                    },
+                    0b01..=0b11 => Some(&BZF0),
+                    _ => None,
+                },
+                0b010 => match self.sq.quarter_code().as_u16() {
+                    0b00 => Some(&MSU0),
+                    0b01 => Some(&QXCH0),
+                    0b10 => Some(&AUG0),
+                    0b11 => Some(&DIM0),
+                    _ => None,
+                },
+                0b011 => match self.st.as_u16() {
+                    0b000 => Some(&DCA0),
+                    0b001 => Some(&DCA1),
+                    _ => None,
+                },
+                0b100 => match self.st.as_u16() {
+                    0b000 => Some(&DCS0),
+                    0b001 => Some(&DCS1),
+                    _ => None,
+                },
+                0b101 => match self.st.as_u16() {
+                    0b000 => Some(&NDX0),
+                    0b001 => Some(&NDX1),
+                    _ => None,
+                },
+                0b110 => match self.sq.quarter_code().as_u16() {
+                    0b00 => Some(&SU0),
+                    0b01..=0b11 => Some(&BZMF0),
+                    _ => None,
+                },
+                0b111 => match self.st.as_u16() {
+                    0b000 => Some(&MP0),
+                    0b001 => Some(&MP1),
+                    0b011 => Some(&MP3),
+                    _ => None,
+                },
+                _ => None,
             }
         }
     }
@@ -236,127 +458,615 @@ impl Cpu {
         for action in actions.iter().filter(|action| action.execute(br)) {
             (action.control_pulse().exec_read_wl)(self, wl);
         }
+
+        if t > TimePulse::T4 {
+            let executes = |pulse: &ControlPulse| {
+                actions
+                    .iter()
+                    .filter(|action| action.execute(br))
+                    .any(|action| std::ptr::eq(action.control_pulse(), pulse))
+            };
+            if executes(&WB) {
+                self.b_holds_g = executes(&RG);
+            }
+            if executes(&WG) {
+                let restore = self.b_holds_g && executes(&RB) && !executes(&WSC);
+                self.g_written |= !restore;
+            }
+        }
     }
 
     /// Run a single step, i.e. a single action
+    ///
+    /// Panics if no subinstruction exists for SQ and ST, see
+    /// `try_step_control_pulse`.
     pub fn step_control_pulse(&mut self) {
+        if let Err(error) = self.try_step_control_pulse() {
+            panic!("{}", error);
+        }
+    }
+
+    /// Run a single step, i.e. a single action
+    ///
+    /// If no subinstruction exists for SQ and ST, the error is returned
+    /// and the CPU is left untouched.
+    pub fn try_step_control_pulse(&mut self) -> Result<(), EmulationError> {
+        self.accesses.clear();
+
+        if self.standby {
+            self.step_standby();
+            return Ok(());
+        }
+
+        self.try_current_subinstruction()?;
+
         // Execute the control pulses
         self.execute_control_pulses(self.current_timepulse);
 
         // Execute additional task
+        let mut alarm = None;
         match self.current_timepulse {
             TimePulse::T1 => {
                 // Save S value
                 self.current_s = self.s;
+                self.b_holds_g = false;
+                self.g_written = false;
             }
+            TimePulse::T4 if self.is_channel_subinstruction() => (),
             TimePulse::T4 => {
                 // Perform erasable memory read
-                match self.current_s.address() {
+                let location = match self.current_s.address() {
                     MemoryAddress::UnswitchedErasableMemory(bank, address) => {
-                        self.g |= self
-                            .erasable_storage
-                            .read(bank, address)
-                            .as_register_value();
-                    }
-                    MemoryAddress::SwitchedErasableMemory(address) => {
-                        self.g |= self
-                            .erasable_storage
-                            .read(self.ebank, address)
-                            .as_register_value();
+                        if bank == W3::zero() && address == W8::from(NEWJOB) {
+                            self.watchdogs.access_newjob();
+                        }
+                        Some((bank, address))
                     }
-                    _ => (),
+                    MemoryAddress::SwitchedErasableMemory(address) => Some((self.ebank, address)),
+                    _ => None,
                 };
+                if let Some((bank, address)) = location {
+                    let word = self.erasable_storage.read(bank, address);
+                    self.accesses
+                        .push(MemoryAccess::ErasableRead(bank, address));
+                    self.g |= word.as_register_value();
+                    if self.parity_check && !word.is_valid() {
+                        alarm = Some(Alarm::ErasableParity);
+                    }
+                }
             }
             TimePulse::T6 => {
                 // Perform fixed memory read
-                match self.current_s.address() {
+                let word = match self.current_s.address() {
                     MemoryAddress::UnswitchedFixedMemory(bank, address) => {
-                        self.g |= self
-                            .fixed_storage
-                            .read(bank.into(), address)
-                            .as_register_value();
+                        Some(self.fixed_storage.read(bank.into(), address))
                     }
-                    MemoryAddress::SwitchedFixedMemory(address) => {
-                        // TODO: take into account super-bit
-                        self.g |= self
-                            .fixed_storage
-                            .read(self.fbank.into(), address)
-                            .as_register_value();
-                    }
-                    _ => (),
+                    // Banks 44 to 47 do not exist, nothing is read
+                    MemoryAddress::SwitchedFixedMemory(address) => self
+                        .fixed_bank()
+                        .map(|bank| self.fixed_storage.read(bank, address)),
+                    _ => None,
                 };
+                if let Some(word) = word {
+                    self.g |= word.as_register_value();
+                    if self.parity_check && !word.is_valid() {
+                        alarm = Some(Alarm::FixedParity);
+                    }
+                }
             }
+            TimePulse::T10 if self.is_channel_subinstruction() => (),
             TimePulse::T10 => {
                 // Perform erasable memory write
-                match self.current_s.address() {
-                    MemoryAddress::UnswitchedErasableMemory(bank, address) => {
-                        self.erasable_storage.write(
-                            bank,
-                            address,
-                            MemoryWord::with_proper_parity(self.g.into()),
-                        );
-                    }
-                    MemoryAddress::SwitchedErasableMemory(address) => {
-                        self.erasable_storage.write(
-                            self.ebank,
-                            address,
-                            MemoryWord::with_proper_parity(self.g.into()),
-                        );
-                    }
-                    _ => (),
+                let location = match self.current_s.address() {
+                    MemoryAddress::UnswitchedErasableMemory(bank, address) => Some((bank, address)),
+                    MemoryAddress::SwitchedErasableMemory(address) => Some((self.ebank, address)),
+                    _ => None,
                 };
+                if let Some((bank, address)) = location {
+                    // Otherwise, the word read at T4 is only restored
+                    if self.g_written {
+                        self.accesses
+                            .push(MemoryAccess::ErasableWrite(bank, address));
+                    }
+                    self.erasable_storage.write(
+                        bank,
+                        address,
+                        MemoryWord::from_register_value(self.g),
+                    );
+                }
+            }
+            TimePulse::T12 if self.counter.is_some() => {
+                // End of a counter subinstruction, resume the interrupted sequence
+                self.counter = None;
+                if let Some(context) = self.counter_context.take() {
+                    self.s = context.s;
+                    self.g = context.g;
+                    self.x = context.x;
+                    self.y = context.y;
+                    self.neac = context.neac;
+                    self.br = context.br;
+                }
+                self.end_mct();
             }
             TimePulse::T12 => {
+                // RESUME ends the interrupt service routine
+                if std::ptr::eq(self.current_subinstruction(), &RSM3) {
+                    self.interrupt_in_progress = false;
+                }
+
+                // EDRUPT starts an interrupt service routine, even if
+                // interrupts are not allowed
+                if std::ptr::eq(self.current_subinstruction(), &EDRUPT0) {
+                    self.interrupt_in_progress = true;
+                }
+
+                // The extended INDEX sets FUTEXT again, so that the indexed
+                // instruction is also extended
+                if self.nisq && self.sq.is_extended() && self.sq.order_code() == W3::from(0b101) {
+                    self.ext = true;
+                }
+
                 // Set stage counter
                 self.st = self.next_st;
                 self.next_st = W3::zero();
 
                 // If NISQ was triggered, load next instruction into SQ
                 // This is the equivalent of control pulses RB and WSQ
-                // TODO: should also re-enable some interrupts
                 if self.nisq {
-                    self.sq = SequenceRegister::new(W6::from(self.b >> 9), self.ext);
                     self.nisq = false;
 
-                    // Reset FUTEXT only once we have executed the extended instruction
-                    // (instructions with ST=0b010 are _not_ extended instructions)
-                    if self.st != W3::from(0b010) {
-                        self.ext = false;
+                    if self.is_interrupt_allowed() {
+                        // Execute RUPT instead, the next instruction is saved in BRUPT
+                        self.rupt = true;
+                        self.interrupt_in_progress = true;
+                    } else {
+                        self.sq = SequenceRegister::new(W6::from(self.b >> 9), self.ext);
+                        self.rupt = false;
+                        self.watchdogs.instruction(self.sq.is_transfer_control());
+
+                        // Reset FUTEXT only once we have executed the extended instruction
+                        // (instructions with ST=0b010 are _not_ extended instructions)
+                        if self.st != W3::from(0b010) {
+                            self.ext = false;
+                        }
                     }
                 }
 
-                // Reset the carry flip-flop
-                self.ci = false;
+                self.end_mct();
             }
             _ => (),
         }
 
         // Increment timepulse counter
         self.current_timepulse = self.current_timepulse.next();
+
+        // TIME6 is enabled by channel 13 bit 15
+        let time6_enabled = self.channels.read(W9::from(0o13)).get(14);
+        self.scaler.tick(&mut self.counters, time6_enabled);
+
+        self.watchdogs.rupt(self.interrupt_in_progress);
+        if let Some(watchdog) = self.watchdogs.tick(self.scaler.timepulses()) {
+            if self.restart_alarms {
+                alarm = Some(watchdog);
+            }
+        }
+
+        if let Some(alarm) = alarm {
+            self.alarm_restart(alarm);
+        }
+
+        Ok(())
+    }
+
+    /// Restart the computer after a hardware alarm
+    ///
+    /// The cause is latched in channel 77, then GOJAM is performed.
+    fn alarm_restart(&mut self, alarm: Alarm) {
+        self.alarm = Some(alarm);
+        let restart_channel = W9::from(RESTART_CHANNEL);
+        self.channels[restart_channel] |= alarm.channel_bits();
+
+        self.gojam();
+    }
+
+    /// Power the computer on
+    ///
+    /// All the flip-flop registers, the channels, the scaler and the alarm
+    /// circuits are cleared, then GOJAM is performed. Erasable memory is
+    /// made of magnetic cores, so it keeps its content.
+    pub fn power_on(&mut self) {
+        self.a = W16::zero();
+        self.l = W16::zero();
+        self.q = W16::zero();
+        self.z = W16::zero();
+        self.ebank = W3::zero();
+        self.fbank = W5::zero();
+        self.b = W16::zero();
+        self.g = W16::zero();
+        self.x = W16::zero();
+        self.y = W16::zero();
+        self.channels = initial_channels();
+        self.scaler = Scaler::new();
+        self.alarm = None;
+        self.watchdogs = Watchdogs::new();
+
+        self.gojam();
+    }
+
+    /// External reset (STRT signal)
+    ///
+    /// GOJAM is performed without latching a cause in channel 77. Central
+    /// registers and erasable memory keep their content.
+    pub fn reset(&mut self) {
+        self.gojam();
+    }
+
+    /// General reset, executed on power-on, on a hardware alarm and on an
+    /// external reset
+    ///
+    /// The sequence generator is reset so that GOJ1 is executed at the next
+    /// MCT, which transfers control to address 4000. Pending interrupts and
+    /// counter requests are dropped, interrupts are allowed again and the
+    /// output channels are cleared, including the superbank bit.
+    fn gojam(&mut self) {
+        self.standby = false;
+
+        // S is cleared so that GOJ1 does not read the failed address again
+        self.current_timepulse = TimePulse::T1;
+        self.s = AddressRegister::zero();
+        self.sq = SequenceRegister::new(W6::zero(), false);
+        self.st = W3::from(0o1);
+        self.next_st = W3::zero();
+        self.br = BranchRegister::new();
+        self.ci = false;
+        self.neac = false;
+        self.nisq = false;
+        self.ext = false;
+        self.inhibit_interrupts = false;
+        self.interrupts = InterruptRequests::new();
+        self.rupt = false;
+        self.vectored_interrupt = None;
+        self.interrupt_in_progress = false;
+        self.counters = CounterRequests::new();
+        self.counter = None;
+        self.counter_context = None;
+
+        for channel in RESET_CHANNELS.iter() {
+            self.output_channel(W9::from(*channel), W15::zero());
+        }
+    }
+
+    /// Advance the timepulse and the scaler while in standby
+    ///
+    /// The sequence generator is halted, so the counter requests are not
+    /// serviced by counter subinstructions. TIME1 is incremented directly,
+    /// with its overflow into TIME2, and the other requests are dropped.
+    fn step_standby(&mut self) {
+        self.current_timepulse = self.current_timepulse.next();
+
+        let mut requests = CounterRequests::new();
+        self.scaler.tick(&mut requests, false);
+
+        let mut carry = false;
+        while let Some((cell, _)) = requests.take_highest() {
+            if cell == counters::TIME1 {
+                carry = self.standby_increment(counters::TIME1);
+            }
+        }
+        if carry {
+            self.standby_increment(counters::TIME2);
+        }
+    }
+
+    /// Increment a timer in erasable memory, returns true on overflow
+    fn standby_increment(&mut self, cell: CounterCell) -> bool {
+        let address = W8::from(cell.address());
+        let value = self
+            .erasable_storage
+            .read(W3::zero(), address)
+            .as_register_value();
+        let sum = adder::add(value, W16::from(0o1), false, false);
+        self.erasable_storage
+            .write(W3::zero(), address, MemoryWord::from_register_value(sum));
+        self.accesses
+            .push(MemoryAccess::ErasableWrite(W3::zero(), address));
+        sum.get(14) && !sum.get(15)
+    }
+
+    /// Press the PRO key of the DSKY (channel 32 bit 14)
+    ///
+    /// The input is active-low, so bit 14 reads ZERO while the key is
+    /// pressed. When standby is allowed by channel 13 bit 11, the computer
+    /// enters standby. In standby, the computer leaves it and restarts.
+    pub fn press_pro(&mut self) {
+        let channel = W9::from(PRO_CHANNEL);
+        self.channels[channel].set(13, false);
+
+        if self.standby {
+            self.gojam();
+        } else if self.channels.read(W9::from(0o13)).get(10) {
+            self.standby = true;
+        }
+    }
+
+    /// Release the PRO key of the DSKY
+    pub fn release_pro(&mut self) {
+        let channel = W9::from(PRO_CHANNEL);
+        self.channels[channel].set(13, true);
+    }
+
+    /// Returns true while the computer is in standby
+    pub fn is_standby(&self) -> bool {
+        self.standby
+    }
+
+    /// Alarm which caused the last restart
+    ///
+    /// It is cleared when the program writes channel 77.
+    pub fn alarm(&self) -> Option<Alarm> {
+        self.alarm
+    }
+
+    /// Enable or disable the parity alarm (enabled by default)
+    pub fn set_parity_check(&mut self, enabled: bool) {
+        self.parity_check = enabled;
+    }
+
+    /// Enable or disable the Night Watchman, TC Trap and Rupt Lock alarms
+    /// (disabled by default)
+    ///
+    /// When enabled, a program which does not test NEWJOB, loops on a TC or
+    /// stays too long in or out of interrupts is restarted, as on the real
+    /// computer. Small test programs usually trigger them, so they are only
+    /// enabled on request.
+    pub fn set_restart_alarms(&mut self, enabled: bool) {
+        self.restart_alarms = enabled;
+    }
+
+    /// Erasable memory and channel accesses made by the last control pulse
+    ///
+    /// Erasable memory is read at T4 and written back at T10. A write is
+    /// only reported when the subinstruction wrote G after the read, other
+    /// than to restore the word read, even if the word is unchanged.
+    pub fn accesses(&self) -> &[MemoryAccess] {
+        &self.accesses
+    }
+
+    /// Address of the instruction starting at the current control pulse
+    ///
+    /// Returns `None` unless the first MCT of an instruction is about to be
+    /// executed. EXTEND, INHINT and RELINT have no subinstruction of their
+    /// own, their execution overlaps with the next instruction fetch.
+    pub fn instruction_start(&self) -> Option<W12> {
+        let starting = self.current_timepulse == TimePulse::T1
+            && self.counter.is_none()
+            && !self.rupt
+            && self.st == W3::zero();
+
+        // Z was incremented when the instruction was fetched
+        if starting {
+            Some(W12::from(self.z.as_u16().wrapping_sub(1)))
+        } else {
+            None
+        }
+    }
+
+    /// Read a word of erasable memory, including its parity bit
+    pub fn read_erasable(&self, bank: W3, address: W8) -> MemoryWord {
+        self.erasable_storage.read(bank, address)
+    }
+
+    /// Write a word of erasable memory, including its parity bit
+    pub fn write_erasable(&mut self, bank: W3, address: W8, value: MemoryWord) {
+        self.erasable_storage.write(bank, address, value);
+    }
+
+    /// Tasks performed at the end of every MCT
+    fn end_mct(&mut self) {
+        // Reset the carry flip-flop
+        self.ci = false;
+
+        let mut requests = Requests::new();
+        for (_, peripheral) in self.peripherals.iter_mut() {
+            peripheral.tick(&mut self.channels, &mut requests);
+        }
+        for (cell, instruction) in requests.counters {
+            self.counters.request(cell, instruction);
+        }
+        for interrupt in requests.interrupts {
+            self.interrupts.request(interrupt);
+        }
+
+        // Service the counter with the highest priority in the next MCT
+        if let Some(counter) = self.counters.take_highest() {
+            self.counter = Some(counter);
+            self.counter_context = Some(CounterContext {
+                s: self.s,
+                g: self.g,
+                x: self.x,
+                y: self.y,
+                neac: self.neac,
+                br: self.br,
+            });
+        }
     }
 
     /// Run a single subinstruction, i.e. a single MCT
+    ///
+    /// Panics if no subinstruction exists for SQ and ST, see
+    /// `try_step_subinstruction`.
     pub fn step_subinstruction(&mut self) {
+        if let Err(error) = self.try_step_subinstruction() {
+            panic!("{}", error);
+        }
+    }
+
+    /// Run a single subinstruction, i.e. a single MCT
+    ///
+    /// If no subinstruction exists for SQ and ST, the error is returned
+    /// before the MCT is started and the CPU is left at T1.
+    pub fn try_step_subinstruction(&mut self) -> Result<(), EmulationError> {
         // execute at least one control pulse
-        self.step_control_pulse();
+        self.try_step_control_pulse()?;
 
         // continue until we read T1
         while self.current_timepulse != TimePulse::T1 {
-            self.step_control_pulse();
+            self.try_step_control_pulse()?;
         }
+
+        Ok(())
     }
 
     pub fn current_subsintruction_name(&self) -> &'static str {
         self.current_subinstruction().name
     }
 
+    /// Read an I/O channel
+    ///
+    /// Channels 1 and 2 are registers L and Q. Their 15-bit value is
+    /// taken with bit 16 (the sign) in bit position 15.
+    pub fn read_channel(&self, channel: W9) -> W15 {
+        match channel.as_u16() {
+            0o1 => MemoryWord::from_register_value(self.l).value(),
+            0o2 => MemoryWord::from_register_value(self.q).value(),
+            _ => self.channels.read(channel),
+        }
+    }
+
+    /// Write an I/O channel
+    ///
+    /// Channels 1 and 2 are registers L and Q. The value is sign-extended
+    /// to 16 bits when written to them.
+    pub fn write_channel(&mut self, channel: W9, value: W15) {
+        match channel.as_u16() {
+            0o1 => self.l = MemoryWord::with_proper_parity(value).as_register_value(),
+            0o2 => self.q = MemoryWord::with_proper_parity(value).as_register_value(),
+            _ => self.channels.write(channel, value),
+        }
+    }
+
+    /// Request an interrupt
+    ///
+    /// The interrupt stays pending until its RUPT sequence is executed.
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupts.request(interrupt);
+    }
+
+    /// Number of timepulses counted by the scaler since power-on
+    ///
+    /// There are 1 024 000 timepulses per second.
+    pub fn timepulses(&self) -> u64 {
+        self.scaler.timepulses()
+    }
+
+    /// Request a counter instruction on a counter cell
+    ///
+    /// Each request is serviced by one counter subinstruction, inserted
+    /// between two MCTs. A request identical to one still pending on the
+    /// cell is merged with it. See the `counters` module for the cells.
+    pub fn request_counter(&mut self, cell: CounterCell, instruction: CounterInstruction) {
+        self.counters.request(cell, instruction);
+    }
+
+    /// Returns true if the interrupt was requested and not yet taken
+    pub fn is_interrupt_pending(&self, interrupt: Interrupt) -> bool {
+        self.interrupts.is_pending(interrupt)
+    }
+
+    /// Returns true while an interrupt service routine is executing
+    pub fn is_interrupt_in_progress(&self) -> bool {
+        self.interrupt_in_progress
+    }
+
+    /// Returns true if a pending interrupt can be taken at this NISQ
+    ///
+    /// Interrupts are not taken while INHINT is set, while another one is
+    /// in progress, before an extended instruction, when A holds an
+    /// overflow, or when the next instruction was not fetched (ST2 was
+    /// set by RAD).
+    fn is_interrupt_allowed(&self) -> bool {
+        self.interrupts.highest().is_some()
+            && !self.inhibit_interrupts
+            && !self.interrupt_in_progress
+            && !self.ext
+            && self.a.get(15) == self.a.get(14)
+            && self.st != W3::from(0b010)
+    }
+
+    /// Attach a peripheral to a range of channels
+    ///
+    /// Reads of a channel are answered by the first peripheral attached
+    /// to it, while writes are reported to all of them. Channels 1 and 2
+    /// are registers L and Q and are never dispatched to peripherals.
+    pub fn attach_peripheral(
+        &mut self,
+        channels: RangeInclusive<W9>,
+        peripheral: Box<dyn Peripheral>,
+    ) {
+        self.peripherals.push((channels, peripheral));
+    }
+
+    /// Read an I/O channel for control pulse RCH
+    fn input_channel(&mut self, channel: W9) -> W15 {
+        let stored = self.channels.read(channel);
+        match self
+            .peripherals
+            .iter_mut()
+            .find(|(range, _)| range.contains(&channel))
+        {
+            Some((_, peripheral)) => peripheral.read(channel, stored),
+            None => stored,
+        }
+    }
+
+    /// Write an I/O channel for control pulse WCH
+    fn output_channel(&mut self, channel: W9, value: W15) {
+        // Writing channel 77 resets the restart cause, whatever the value
+        if channel.as_u16() == RESTART_CHANNEL {
+            self.channels.write(channel, W15::zero());
+            self.alarm = None;
+            return;
+        }
+
+        self.channels.write(channel, value);
+        for (range, peripheral) in self.peripherals.iter_mut() {
+            if range.contains(&channel) {
+                peripheral.write(channel, value);
+            }
+        }
+    }
+
+    /// Fixed bank selected for switched fixed memory
+    ///
+    /// When the superbank bit (FEB, channel 7 bit 7) is set, FBANK 30 to
+    /// 37 select banks 40 to 47 instead. Returns `None` for the banks
+    /// which do not exist.
+    fn fixed_bank(&self) -> Option<W6> {
+        let mut bank = W6::from(self.fbank);
+        if self.channels.read(W9::from(0o7)).get(6) && bank.as_u16() >= 0o30 {
+            bank = W6::from(bank.as_u16() + 0o10);
+        }
+
+        if (bank.as_u16() as usize) < FIXED_NUM_BANKS {
+            Some(bank)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the subinstruction being executed addresses a channel.
+    ///
+    /// The channel address is held in S, so the erasable memory cycle is
+    /// inhibited.
+    fn is_channel_subinstruction(&self) -> bool {
+        self.counter.is_none()
+            && !self.rupt
+            && self.st != W3::from(0b010)
+            && self.sq.is_channel_instruction()
+    }
+
     // Read content of the adder unit
     fn u(&self) -> W16 {
-        // TODO: do the actual calculation here, this is way too imprecise
-        let mut result = self.x.as_u16() + self.y.as_u16();
-        if self.ci {
-            result += 1;
-        }
-        W16::from(result)
+        adder::add(self.x, self.y, self.ci, self.neac)
     }
 }
@@ -21,7 +21,7 @@ impl SequenceRegister {
         self.inner.get(6)
     }
 
-    /// Returns the first 3 bits 
+    /// Returns the first 3 bits
     pub fn order_code(&self) -> W3 {
         (self.inner >> 3).into()
     }
@@ -44,6 +44,24 @@ impl SequenceRegister {
         self.inner.into()
     }
 
+    /// Returns true for the channel instructions (READ through RXOR)
+    ///
+    /// These instructions address a channel instead of erasable memory.
+    pub fn is_channel_instruction(&self) -> bool {
+        self.is_extended()
+            && self.order_code() == W3::zero()
+            && self.peripheral_code() != W3::from(0b111)
+    }
+
+    /// Returns true for TC and TCF
+    ///
+    /// These are the instructions monitored by the TC Trap.
+    pub fn is_transfer_control(&self) -> bool {
+        !self.is_extended()
+            && (self.order_code() == W3::zero()
+                || (self.order_code() == W3::from(0b001) && self.quarter_code() != W2::zero()))
+    }
+
     pub fn inner(self) -> W7 {
         self.inner
     }
@@ -0,0 +1,121 @@
+use crate::cpu::counters::{self, CounterInstruction, CounterRequests};
+
+/// Number of timepulses in 10 ms, with the 1.024 MHz clock
+pub const TIMEPULSES_PER_CENTISECOND: u64 = 10240;
+/// Number of timepulses in 1/1600 s
+pub const TIMEPULSES_PER_TIME6_COUNT: u64 = 640;
+
+/// Scaler chain driving the timers
+///
+/// TIME1, TIME3 and TIME5 are incremented every 10 ms. TIME4 is also
+/// incremented every 10 ms, but 5 ms out of phase so that T3RUPT and
+/// T4RUPT do not occur together. TIME6 is decremented every 1/1600 s
+/// while it is enabled by channel 13 bit 15.
+#[derive(Copy, Clone)]
+pub struct Scaler {
+    timepulses: u64,
+}
+
+impl Default for Scaler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scaler {
+    pub fn new() -> Self {
+        Self::with_timepulses(0)
+    }
+
+    /// Scaler which already counted `timepulses` since power-on
+    pub fn with_timepulses(timepulses: u64) -> Self {
+        Self { timepulses }
+    }
+
+    /// Number of timepulses since power-on
+    pub fn timepulses(&self) -> u64 {
+        self.timepulses
+    }
+
+    /// Advance by one timepulse and request the counters which are due
+    pub fn tick(&mut self, requests: &mut CounterRequests, time6_enabled: bool) {
+        self.timepulses += 1;
+
+        match self.timepulses % TIMEPULSES_PER_CENTISECOND {
+            0 => {
+                requests.request(counters::TIME1, CounterInstruction::Pinc);
+                requests.request(counters::TIME3, CounterInstruction::Pinc);
+                requests.request(counters::TIME5, CounterInstruction::Pinc);
+            }
+            phase if phase == TIMEPULSES_PER_CENTISECOND / 2 => {
+                requests.request(counters::TIME4, CounterInstruction::Pinc);
+            }
+            _ => (),
+        }
+
+        if time6_enabled && self.timepulses.is_multiple_of(TIMEPULSES_PER_TIME6_COUNT) {
+            requests.request(counters::TIME6, CounterInstruction::Dinc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::counters::CounterCell;
+
+    fn drain(requests: &mut CounterRequests) -> Vec<(CounterCell, CounterInstruction)> {
+        std::iter::from_fn(|| requests.take_highest()).collect()
+    }
+
+    #[test]
+    fn scaler_timers() {
+        let mut scaler = Scaler::new();
+        let mut requests = CounterRequests::new();
+
+        for _ in 0..TIMEPULSES_PER_CENTISECOND / 2 - 1 {
+            scaler.tick(&mut requests, false);
+        }
+        assert!(!requests.is_pending());
+
+        scaler.tick(&mut requests, false);
+        assert_eq!(
+            drain(&mut requests),
+            [(counters::TIME4, CounterInstruction::Pinc)]
+        );
+
+        for _ in 0..TIMEPULSES_PER_CENTISECOND / 2 {
+            scaler.tick(&mut requests, false);
+        }
+        assert_eq!(
+            drain(&mut requests),
+            [
+                (counters::TIME1, CounterInstruction::Pinc),
+                (counters::TIME3, CounterInstruction::Pinc),
+                (counters::TIME5, CounterInstruction::Pinc),
+            ]
+        );
+        assert_eq!(scaler.timepulses(), TIMEPULSES_PER_CENTISECOND);
+    }
+
+    #[test]
+    fn scaler_time6() {
+        let mut scaler = Scaler::new();
+        let mut requests = CounterRequests::new();
+
+        for _ in 0..TIMEPULSES_PER_TIME6_COUNT {
+            scaler.tick(&mut requests, false);
+        }
+        assert!(!requests.is_pending());
+
+        for _ in 0..3 {
+            for _ in 0..TIMEPULSES_PER_TIME6_COUNT {
+                scaler.tick(&mut requests, true);
+            }
+            assert_eq!(
+                drain(&mut requests),
+                [(counters::TIME6, CounterInstruction::Dinc)]
+            );
+        }
+    }
+}
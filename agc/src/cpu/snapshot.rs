@@ -0,0 +1,656 @@
+use crate::cpu::alarms::{Alarm, Watchdogs};
+use crate::cpu::counters::{CounterCell, CounterContext, CounterInstruction, CounterRequests};
+use crate::cpu::interrupts::{Interrupt, InterruptRequests};
+use crate::cpu::registers::{AddressRegister, BranchRegister, SequenceRegister};
+use crate::cpu::scaler::Scaler;
+use crate::cpu::{Cpu, TimePulse};
+use crate::io::{Channels, NUM_CHANNELS};
+use crate::memory::{ErasableStorage, MemoryWord, ERASABLE_BANK_SIZE, ERASABLE_NUM_BANKS};
+use crate::word::*;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// First bytes of a snapshot file
+const MAGIC: &[u8; 4] = b"AGCS";
+/// Version of the snapshot format, incremented on every change
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// Encoding of the enumerations, by position
+const TIMEPULSES: [TimePulse; 12] = [
+    TimePulse::T1,
+    TimePulse::T2,
+    TimePulse::T3,
+    TimePulse::T4,
+    TimePulse::T5,
+    TimePulse::T6,
+    TimePulse::T7,
+    TimePulse::T8,
+    TimePulse::T9,
+    TimePulse::T10,
+    TimePulse::T11,
+    TimePulse::T12,
+];
+const COUNTER_INSTRUCTIONS: [CounterInstruction; 7] = [
+    CounterInstruction::Pinc,
+    CounterInstruction::Minc,
+    CounterInstruction::Dinc,
+    CounterInstruction::Pcdu,
+    CounterInstruction::Mcdu,
+    CounterInstruction::Shinc,
+    CounterInstruction::Shanc,
+];
+const ALARMS: [Alarm; 5] = [
+    Alarm::ErasableParity,
+    Alarm::FixedParity,
+    Alarm::TcTrap,
+    Alarm::RuptLock,
+    Alarm::NightWatchman,
+];
+
+/// Errors when decoding or restoring a snapshot
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SnapshotError {
+    /// The data does not start with the snapshot magic bytes
+    InvalidMagic,
+    /// The snapshot was written by another version of the format
+    UnsupportedVersion(u16),
+    /// The data ends in the middle of the snapshot
+    Truncated,
+    /// A field holds a value which does not exist
+    InvalidValue(&'static str),
+    /// The snapshot was taken with another fixed storage
+    FixedStorageMismatch { expected: u64, found: u64 },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::InvalidMagic => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Truncated => write!(f, "truncated snapshot"),
+            SnapshotError::InvalidValue(field) => write!(f, "invalid value for {}", field),
+            SnapshotError::FixedStorageMismatch { expected, found } => write!(
+                f,
+                "snapshot taken with fixed storage {:016x}, but the CPU has {:016x}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Complete state of a CPU, at any control pulse
+///
+/// The snapshot holds every register, the hidden ones included, the
+/// erasable storage, the channels and the sequencing state, so that the
+/// CPU continues exactly as it would have, even in the middle of a
+/// subinstruction. The fixed storage is only identified by its hash.
+/// Attached peripherals keep their own state and are not captured.
+#[derive(Clone)]
+pub struct CpuSnapshot {
+    fixed_storage_hash: u64,
+
+    a: W16,
+    l: W16,
+    q: W16,
+    z: W16,
+    ebank: W3,
+    fbank: W5,
+    b: W16,
+    g: W16,
+    s: AddressRegister,
+    sq: SequenceRegister,
+    st: W3,
+    x: W16,
+    y: W16,
+    ci: bool,
+    neac: bool,
+    br: BranchRegister,
+
+    erasable_storage: ErasableStorage,
+    channels: Channels,
+
+    current_timepulse: TimePulse,
+    parity_check: bool,
+    restart_alarms: bool,
+    current_s: AddressRegister,
+    b_holds_g: bool,
+    g_written: bool,
+    nisq: bool,
+    ext: bool,
+    next_st: W3,
+    inhibit_interrupts: bool,
+    interrupts: InterruptRequests,
+    rupt: bool,
+    interrupt_in_progress: bool,
+    counters: CounterRequests,
+    counter: Option<(CounterCell, CounterInstruction)>,
+    counter_context: Option<CounterContext>,
+    scaler: Scaler,
+    alarm: Option<Alarm>,
+    watchdogs: Watchdogs,
+    standby: bool,
+}
+
+impl CpuSnapshot {
+    /// Hash of the fixed storage the snapshot was taken with
+    pub fn fixed_storage_hash(&self) -> u64 {
+        self.fixed_storage_hash
+    }
+
+    /// Serialize the snapshot
+    ///
+    /// The format starts with the magic bytes "AGCS" and the version.
+    /// All the values are little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(MAGIC);
+        w.u16(SNAPSHOT_VERSION);
+        w.u64(self.fixed_storage_hash);
+
+        for register in [self.a, self.l, self.q, self.z].iter() {
+            w.u16(register.as_u16());
+        }
+        w.u16(self.ebank.as_u16());
+        w.u16(self.fbank.as_u16());
+        w.u16(self.b.as_u16());
+        w.u16(self.g.as_u16());
+        w.u16(self.s.inner().as_u16());
+        w.u16(self.sq.inner().as_u16());
+        w.u16(self.st.as_u16());
+        w.u16(self.x.as_u16());
+        w.u16(self.y.as_u16());
+        w.bool(self.ci);
+        w.bool(self.neac);
+        w.u16(self.br.inner().as_u16());
+
+        for bank in self.erasable_storage.banks.iter() {
+            for word in bank.inner.iter() {
+                w.memory_word(*word);
+            }
+        }
+        for channel in self.channels.inner.iter() {
+            w.u16(channel.as_u16());
+        }
+
+        w.u8(usize::from(self.current_timepulse) as u8);
+        w.bool(self.parity_check);
+        w.bool(self.restart_alarms);
+        w.u16(self.current_s.inner().as_u16());
+        w.bool(self.b_holds_g);
+        w.bool(self.g_written);
+        w.bool(self.nisq);
+        w.bool(self.ext);
+        w.u16(self.next_st.as_u16());
+        w.bool(self.inhibit_interrupts);
+        let pending = Interrupt::ALL
+            .iter()
+            .enumerate()
+            .filter(|(_, interrupt)| self.interrupts.is_pending(**interrupt))
+            .fold(0, |bits, (bit, _)| bits | (1 << bit));
+        w.u16(pending);
+        w.bool(self.rupt);
+        w.bool(self.interrupt_in_progress);
+
+        let counters = self.counters.iter().collect::<Vec<_>>();
+        w.u16(counters.len() as u16);
+        for (cell, instruction) in counters {
+            w.counter(cell, instruction);
+        }
+        w.bool(self.counter.is_some());
+        if let Some((cell, instruction)) = self.counter {
+            w.counter(cell, instruction);
+        }
+        w.bool(self.counter_context.is_some());
+        if let Some(context) = self.counter_context {
+            w.u16(context.s.inner().as_u16());
+            w.u16(context.g.as_u16());
+            w.u16(context.x.as_u16());
+            w.u16(context.y.as_u16());
+            w.bool(context.neac);
+            w.u16(context.br.inner().as_u16());
+        }
+
+        w.u64(self.scaler.timepulses());
+        match self.alarm {
+            Some(alarm) => w.u8(ALARMS.iter().position(|a| *a == alarm).unwrap() as u8 + 1),
+            None => w.u8(0),
+        }
+        w.u8(self.watchdogs.to_bits());
+        w.bool(self.standby);
+
+        w.inner
+    }
+
+    /// Deserialize a snapshot written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut r = Reader { bytes };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = r.u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let fixed_storage_hash = r.u64()?;
+
+        let a = W16::from(r.u16()?);
+        let l = W16::from(r.u16()?);
+        let q = W16::from(r.u16()?);
+        let z = W16::from(r.u16()?);
+        let ebank = W3::from(r.u16()?);
+        let fbank = W5::from(r.u16()?);
+        let b = W16::from(r.u16()?);
+        let g = W16::from(r.u16()?);
+        let s = AddressRegister::from(W12::from(r.u16()?));
+        let sq = r.sequence_register()?;
+        let st = W3::from(r.u16()?);
+        let x = W16::from(r.u16()?);
+        let y = W16::from(r.u16()?);
+        let ci = r.bool()?;
+        let neac = r.bool()?;
+        let br = BranchRegister::from(W2::from(r.u16()?));
+
+        let mut erasable_storage = ErasableStorage::new();
+        for bank in 0..ERASABLE_NUM_BANKS {
+            for address in 0..ERASABLE_BANK_SIZE {
+                erasable_storage.banks[bank].inner[address] = r.memory_word()?;
+            }
+        }
+        let mut channels = Channels::new();
+        for channel in 0..NUM_CHANNELS {
+            channels.inner[channel] = W15::from(r.u16()?);
+        }
+
+        let current_timepulse = *r
+            .u8()?
+            .checked_sub(1)
+            .and_then(|index| TIMEPULSES.get(index as usize))
+            .ok_or(SnapshotError::InvalidValue("timepulse"))?;
+        let parity_check = r.bool()?;
+        let restart_alarms = r.bool()?;
+        let current_s = AddressRegister::from(W12::from(r.u16()?));
+        let b_holds_g = r.bool()?;
+        let g_written = r.bool()?;
+        let nisq = r.bool()?;
+        let ext = r.bool()?;
+        let next_st = W3::from(r.u16()?);
+        let inhibit_interrupts = r.bool()?;
+        let pending = r.u16()?;
+        let mut interrupts = InterruptRequests::new();
+        for (bit, interrupt) in Interrupt::ALL.iter().enumerate() {
+            if pending & (1 << bit) != 0 {
+                interrupts.request(*interrupt);
+            }
+        }
+        let rupt = r.bool()?;
+        let interrupt_in_progress = r.bool()?;
+
+        let mut counters = CounterRequests::new();
+        for _ in 0..r.u16()? {
+            let (cell, instruction) = r.counter()?;
+            counters.request(cell, instruction);
+        }
+        let counter = if r.bool()? { Some(r.counter()?) } else { None };
+        let counter_context = if r.bool()? {
+            Some(CounterContext {
+                s: AddressRegister::from(W12::from(r.u16()?)),
+                g: W16::from(r.u16()?),
+                x: W16::from(r.u16()?),
+                y: W16::from(r.u16()?),
+                neac: r.bool()?,
+                br: BranchRegister::from(W2::from(r.u16()?)),
+            })
+        } else {
+            None
+        };
+
+        let scaler = Scaler::with_timepulses(r.u64()?);
+        let alarm = match r.u8()? {
+            0 => None,
+            index => Some(
+                *ALARMS
+                    .get(index as usize - 1)
+                    .ok_or(SnapshotError::InvalidValue("alarm"))?,
+            ),
+        };
+        let watchdogs = Watchdogs::from_bits(r.u8()?);
+        let standby = r.bool()?;
+
+        if !r.bytes.is_empty() {
+            return Err(SnapshotError::InvalidValue("trailing data"));
+        }
+
+        Ok(Self {
+            fixed_storage_hash,
+            a,
+            l,
+            q,
+            z,
+            ebank,
+            fbank,
+            b,
+            g,
+            s,
+            sq,
+            st,
+            x,
+            y,
+            ci,
+            neac,
+            br,
+            erasable_storage,
+            channels,
+            current_timepulse,
+            parity_check,
+            restart_alarms,
+            current_s,
+            b_holds_g,
+            g_written,
+            nisq,
+            ext,
+            next_st,
+            inhibit_interrupts,
+            interrupts,
+            rupt,
+            interrupt_in_progress,
+            counters,
+            counter,
+            counter_context,
+            scaler,
+            alarm,
+            watchdogs,
+            standby,
+        })
+    }
+
+    /// Write the snapshot to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        File::create(path)?.write_all(&self.to_bytes())
+    }
+
+    /// Read a snapshot from a file written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Ok(Self::from_bytes(&bytes)?)
+    }
+}
+
+impl Cpu {
+    /// Capture the complete state of the CPU
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            fixed_storage_hash: self.fixed_storage_hash,
+            a: self.a,
+            l: self.l,
+            q: self.q,
+            z: self.z,
+            ebank: self.ebank,
+            fbank: self.fbank,
+            b: self.b,
+            g: self.g,
+            s: self.s,
+            sq: self.sq,
+            st: self.st,
+            x: self.x,
+            y: self.y,
+            ci: self.ci,
+            neac: self.neac,
+            br: self.br,
+            erasable_storage: self.erasable_storage.clone(),
+            channels: self.channels.clone(),
+            current_timepulse: self.current_timepulse,
+            parity_check: self.parity_check,
+            restart_alarms: self.restart_alarms,
+            current_s: self.current_s,
+            b_holds_g: self.b_holds_g,
+            g_written: self.g_written,
+            nisq: self.nisq,
+            ext: self.ext,
+            next_st: self.next_st,
+            inhibit_interrupts: self.inhibit_interrupts,
+            interrupts: self.interrupts,
+            rupt: self.rupt,
+            interrupt_in_progress: self.interrupt_in_progress,
+            counters: self.counters.clone(),
+            counter: self.counter,
+            counter_context: self.counter_context,
+            scaler: self.scaler,
+            alarm: self.alarm,
+            watchdogs: self.watchdogs,
+            standby: self.standby,
+        }
+    }
+
+    /// Restore a state captured by `snapshot`
+    ///
+    /// The snapshot must have been taken with the same fixed storage.
+    /// Attached peripherals are left as they are.
+    pub fn restore(&mut self, snapshot: &CpuSnapshot) -> Result<(), SnapshotError> {
+        if snapshot.fixed_storage_hash != self.fixed_storage_hash {
+            return Err(SnapshotError::FixedStorageMismatch {
+                expected: snapshot.fixed_storage_hash,
+                found: self.fixed_storage_hash,
+            });
+        }
+
+        self.a = snapshot.a;
+        self.l = snapshot.l;
+        self.q = snapshot.q;
+        self.z = snapshot.z;
+        self.ebank = snapshot.ebank;
+        self.fbank = snapshot.fbank;
+        self.b = snapshot.b;
+        self.g = snapshot.g;
+        self.s = snapshot.s;
+        self.sq = snapshot.sq;
+        self.st = snapshot.st;
+        self.x = snapshot.x;
+        self.y = snapshot.y;
+        self.ci = snapshot.ci;
+        self.neac = snapshot.neac;
+        self.br = snapshot.br;
+        self.erasable_storage = snapshot.erasable_storage.clone();
+        self.channels = snapshot.channels.clone();
+        self.current_timepulse = snapshot.current_timepulse;
+        self.parity_check = snapshot.parity_check;
+        self.restart_alarms = snapshot.restart_alarms;
+        self.accesses.clear();
+        self.current_s = snapshot.current_s;
+        self.b_holds_g = snapshot.b_holds_g;
+        self.g_written = snapshot.g_written;
+        self.nisq = snapshot.nisq;
+        self.ext = snapshot.ext;
+        self.next_st = snapshot.next_st;
+        self.inhibit_interrupts = snapshot.inhibit_interrupts;
+        self.interrupts = snapshot.interrupts;
+        self.rupt = snapshot.rupt;
+        self.interrupt_in_progress = snapshot.interrupt_in_progress;
+        self.counters = snapshot.counters.clone();
+        self.counter = snapshot.counter;
+        self.counter_context = snapshot.counter_context;
+        self.scaler = snapshot.scaler;
+        self.alarm = snapshot.alarm;
+        self.watchdogs = snapshot.watchdogs;
+        self.standby = snapshot.standby;
+
+        Ok(())
+    }
+}
+
+/// Little-endian encoder
+#[derive(Default)]
+struct Writer {
+    inner: Vec<u8>,
+}
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.inner.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.inner.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    /// Value in bits 1 to 15, parity in bit 16
+    fn memory_word(&mut self, word: MemoryWord) {
+        self.u16(word.value().as_u16() | ((word.parity() as u16) << 15));
+    }
+
+    fn counter(&mut self, cell: CounterCell, instruction: CounterInstruction) {
+        self.u16(cell.address());
+        let index = COUNTER_INSTRUCTIONS
+            .iter()
+            .position(|i| *i == instruction)
+            .unwrap();
+        self.u8(index as u8);
+    }
+}
+
+/// Little-endian decoder
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::InvalidValue("flag")),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        let mut buf = [0; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn memory_word(&mut self) -> Result<MemoryWord, SnapshotError> {
+        let raw = self.u16()?;
+        Ok(MemoryWord::new(W15::from(raw), raw & 0x8000 != 0))
+    }
+
+    /// Bit 7 is the extend flag
+    fn sequence_register(&mut self) -> Result<SequenceRegister, SnapshotError> {
+        let raw = W7::from(self.u16()?);
+        Ok(SequenceRegister::new(W6::from(raw), raw.get(6)))
+    }
+
+    fn counter(&mut self) -> Result<(CounterCell, CounterInstruction), SnapshotError> {
+        let cell =
+            CounterCell::new(self.u16()?).ok_or(SnapshotError::InvalidValue("counter address"))?;
+        let instruction = *COUNTER_INSTRUCTIONS
+            .get(self.u8()? as usize)
+            .ok_or(SnapshotError::InvalidValue("counter instruction"))?;
+        Ok((cell, instruction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::FixedStorage;
+
+    fn snapshot() -> CpuSnapshot {
+        let mut cpu = Cpu::new(FixedStorage::new());
+        cpu.a = W16::from(0o123456);
+        cpu.request_interrupt(Interrupt::Radar);
+        cpu.request_counter(crate::cpu::counters::PIPAX, CounterInstruction::Minc);
+        cpu.write_channel(W9::from(0o30), W15::from(0o77777));
+        cpu.write_erasable(
+            W3::from(7),
+            W8::from(0o377),
+            MemoryWord::with_wrong_parity(W15::from(0o12345)),
+        );
+        cpu.step_control_pulse();
+        cpu.snapshot()
+    }
+
+    #[test]
+    fn snapshot_bytes() {
+        let bytes = snapshot().to_bytes();
+        assert_eq!(&bytes[..4], b"AGCS");
+        assert_eq!(&bytes[4..6], &SNAPSHOT_VERSION.to_le_bytes());
+
+        let decoded = CpuSnapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.a, W16::from(0o123456));
+        assert_eq!(decoded.current_timepulse, TimePulse::T2);
+        assert!(decoded.interrupts.is_pending(Interrupt::Radar));
+        assert!(!decoded
+            .erasable_storage
+            .read(W3::from(7), W8::from(0o377))
+            .is_valid());
+    }
+
+    #[test]
+    fn snapshot_invalid_bytes() {
+        let bytes = snapshot().to_bytes();
+
+        let mut invalid = bytes.clone();
+        invalid[0] = b'X';
+        assert_eq!(
+            CpuSnapshot::from_bytes(&invalid).err(),
+            Some(SnapshotError::InvalidMagic)
+        );
+
+        let mut invalid = bytes.clone();
+        invalid[4] = 0xFF;
+        assert_eq!(
+            CpuSnapshot::from_bytes(&invalid).err(),
+            Some(SnapshotError::UnsupportedVersion(0x00FF))
+        );
+
+        assert_eq!(
+            CpuSnapshot::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(SnapshotError::Truncated)
+        );
+
+        let mut invalid = bytes;
+        invalid.push(0);
+        assert_eq!(
+            CpuSnapshot::from_bytes(&invalid).err(),
+            Some(SnapshotError::InvalidValue("trailing data"))
+        );
+    }
+}
@@ -0,0 +1,455 @@
+use crate::cpu::Cpu;
+use crate::io::NUM_CHANNELS;
+use crate::memory::ERASABLE_NUM_BANKS;
+use crate::word::{W3, W8, W9};
+use std::fmt;
+use std::str::FromStr;
+
+/// Error in the text of an expression
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExpressionError {
+    /// The character cannot start a token
+    InvalidCharacter(char),
+    /// The number is not octal or does not fit in 64 bits
+    InvalidNumber(String),
+    /// The name is not a register, an erasable location, a channel or `HITS`
+    UnknownName(String),
+    /// The erasable location or the channel does not exist
+    InvalidLocation(String),
+    /// The token is not expected at this place
+    UnexpectedToken(String),
+    /// The expression is incomplete
+    UnexpectedEnd,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpressionError::InvalidCharacter(c) => write!(f, "invalid character '{}'", c),
+            ExpressionError::InvalidNumber(number) => write!(f, "invalid octal number {}", number),
+            ExpressionError::UnknownName(name) => write!(f, "unknown name {}", name),
+            ExpressionError::InvalidLocation(location) => {
+                write!(f, "{} does not exist", location)
+            }
+            ExpressionError::UnexpectedToken(token) => write!(f, "unexpected {}", token),
+            ExpressionError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// Register which can be read in an expression
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Register {
+    A,
+    L,
+    Q,
+    Z,
+    Ebank,
+    Fbank,
+    B,
+    G,
+    S,
+    Sq,
+    St,
+    Br,
+}
+
+impl Register {
+    const NAMES: [(&'static str, Register); 12] = [
+        ("A", Register::A),
+        ("L", Register::L),
+        ("Q", Register::Q),
+        ("Z", Register::Z),
+        ("EBANK", Register::Ebank),
+        ("FBANK", Register::Fbank),
+        ("B", Register::B),
+        ("G", Register::G),
+        ("S", Register::S),
+        ("SQ", Register::Sq),
+        ("ST", Register::St),
+        ("BR", Register::Br),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, register)| *register)
+    }
+
+    fn read(self, cpu: &Cpu) -> u16 {
+        match self {
+            Register::A => cpu.a.as_u16(),
+            Register::L => cpu.l.as_u16(),
+            Register::Q => cpu.q.as_u16(),
+            Register::Z => cpu.z.as_u16(),
+            Register::Ebank => cpu.ebank.as_u16(),
+            Register::Fbank => cpu.fbank.as_u16(),
+            Register::B => cpu.b.as_u16(),
+            Register::G => cpu.g.as_u16(),
+            Register::S => cpu.s.inner().as_u16(),
+            Register::Sq => cpu.sq.inner().as_u16(),
+            Register::St => cpu.st.as_u16(),
+            Register::Br => cpu.br.inner().as_u16(),
+        }
+    }
+}
+
+/// Binary operator, from the lowest to the highest precedence
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+}
+
+impl Operator {
+    fn precedence(self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessOrEqual
+            | Operator::Greater
+            | Operator::GreaterOrEqual => 3,
+            Operator::BitOr => 4,
+            Operator::BitXor => 5,
+            Operator::BitAnd => 6,
+        }
+    }
+
+    fn apply(self, left: u64, right: u64) -> u64 {
+        match self {
+            Operator::Or => u64::from(left != 0 || right != 0),
+            Operator::And => u64::from(left != 0 && right != 0),
+            Operator::Equal => u64::from(left == right),
+            Operator::NotEqual => u64::from(left != right),
+            Operator::Less => u64::from(left < right),
+            Operator::LessOrEqual => u64::from(left <= right),
+            Operator::Greater => u64::from(left > right),
+            Operator::GreaterOrEqual => u64::from(left >= right),
+            Operator::BitOr => left | right,
+            Operator::BitXor => left ^ right,
+            Operator::BitAnd => left & right,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Operator::Or => "||",
+            Operator::And => "&&",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::BitOr => "|",
+            Operator::BitXor => "^",
+            Operator::BitAnd => "&",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// Expression evaluated against the state of the CPU
+///
+/// The syntax is close to the one of Rust:
+/// - numbers are always octal, e.g. `1400`
+/// - registers are `A`, `L`, `Q`, `Z`, `EBANK`, `FBANK`, `B`, `G`, `S`,
+///   `SQ`, `ST` and `BR`, read with all their bits (16 for A)
+/// - `E3,1400` is the erasable word at address 1400 of bank 3, without its
+///   parity bit. The address can also be given as an offset in the bank,
+///   so `E0,0061` is the unswitched address 61.
+/// - `CH30` is channel 30
+/// - `HITS` is the number of times the breakpoint or the watchpoint of the
+///   condition was reached, the current one included
+/// - `&`, `^` and `|` apply bit masks, `==`, `!=`, `<`, `<=`, `>` and `>=`
+///   compare, `&&`, `||` and `!` combine conditions, from the highest to the
+///   lowest precedence
+///
+/// Names are case insensitive. A condition is true when its value is not 0.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expression {
+    Literal(u64),
+    Register(Register),
+    Erasable(W3, W8),
+    Channel(W9),
+    Hits,
+    Not(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Value of the expression, with `hits` for `HITS`
+    pub fn evaluate(&self, cpu: &Cpu, hits: u64) -> u64 {
+        match self {
+            Expression::Literal(value) => *value,
+            Expression::Register(register) => u64::from(register.read(cpu)),
+            Expression::Erasable(bank, address) => {
+                u64::from(cpu.read_erasable(*bank, *address).value().as_u16())
+            }
+            Expression::Channel(channel) => u64::from(cpu.read_channel(*channel).as_u16()),
+            Expression::Hits => hits,
+            Expression::Not(expression) => u64::from(expression.evaluate(cpu, hits) == 0),
+            Expression::Binary(operator, left, right) => {
+                operator.apply(left.evaluate(cpu, hits), right.evaluate(cpu, hits))
+            }
+        }
+    }
+
+    /// Returns true if the value of the expression is not 0
+    pub fn is_true(&self, cpu: &Cpu, hits: u64) -> bool {
+        self.evaluate(cpu, hits) != 0
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+        let expression = parser.expression(0)?;
+        match parser.next() {
+            None => Ok(expression),
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    Number(u64),
+    Name(String),
+    Operator(Operator),
+    Not,
+    Comma,
+    OpenParenthesis,
+    CloseParenthesis,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{:o}", value),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Operator(operator) => write!(f, "{}", operator),
+            Token::Not => write!(f, "!"),
+            Token::Comma => write!(f, ","),
+            Token::OpenParenthesis => write!(f, "("),
+            Token::CloseParenthesis => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            _ if c.is_whitespace() => continue,
+            ',' => Token::Comma,
+            '(' => Token::OpenParenthesis,
+            ')' => Token::CloseParenthesis,
+            '^' => Token::Operator(Operator::BitXor),
+            '&' if chars.next_if_eq(&'&').is_some() => Token::Operator(Operator::And),
+            '&' => Token::Operator(Operator::BitAnd),
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Operator(Operator::Or),
+            '|' => Token::Operator(Operator::BitOr),
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Equal),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::NotEqual),
+            '!' => Token::Not,
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::LessOrEqual),
+            '<' => Token::Operator(Operator::Less),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::GreaterOrEqual),
+            '>' => Token::Operator(Operator::Greater),
+            _ if c.is_ascii_alphanumeric() => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric()) {
+                    word.push(c);
+                }
+                if c.is_ascii_digit() {
+                    let value = u64::from_str_radix(&word, 8)
+                        .map_err(|_| ExpressionError::InvalidNumber(word))?;
+                    Token::Number(value)
+                } else {
+                    Token::Name(word.to_ascii_uppercase())
+                }
+            }
+            _ => return Err(ExpressionError::InvalidCharacter(c)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ExpressionError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    /// Parse the operators with at least `min_precedence`
+    fn expression(&mut self, min_precedence: u8) -> Result<Expression, ExpressionError> {
+        let mut left = self.unary()?;
+        while let Some(Token::Operator(operator)) = self.tokens.get(self.position) {
+            let operator = *operator;
+            if operator.precedence() < min_precedence {
+                break;
+            }
+            self.position += 1;
+            let right = self.expression(operator.precedence() + 1)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        match self.next().ok_or(ExpressionError::UnexpectedEnd)? {
+            Token::Not => Ok(Expression::Not(Box::new(self.unary()?))),
+            Token::Number(value) => Ok(Expression::Literal(value)),
+            Token::OpenParenthesis => {
+                let expression = self.expression(0)?;
+                self.expect(Token::CloseParenthesis)?;
+                Ok(expression)
+            }
+            Token::Name(name) => self.name(name),
+            token => Err(ExpressionError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn name(&mut self, name: String) -> Result<Expression, ExpressionError> {
+        if name == "HITS" {
+            return Ok(Expression::Hits);
+        }
+        if let Some(register) = Register::from_name(&name) {
+            return Ok(Expression::Register(register));
+        }
+
+        let octal = |text: &str| u16::from_str_radix(text, 8).ok();
+        if let Some(channel) = name.strip_prefix("CH").and_then(octal) {
+            return if usize::from(channel) < NUM_CHANNELS {
+                Ok(Expression::Channel(W9::from(channel)))
+            } else {
+                Err(ExpressionError::InvalidLocation(name))
+            };
+        }
+        if let Some(bank) = name.strip_prefix('E').and_then(octal) {
+            self.expect(Token::Comma)?;
+            let address = match self.next() {
+                Some(Token::Number(address)) => address,
+                Some(token) => return Err(ExpressionError::UnexpectedToken(token.to_string())),
+                None => return Err(ExpressionError::UnexpectedEnd),
+            };
+            // Switched erasable addresses, or offsets in the bank
+            let offset = match address {
+                0o1400..=0o1777 => Some(address - 0o1400),
+                0o0..=0o377 => Some(address),
+                _ => None,
+            };
+            return match offset {
+                Some(offset) if usize::from(bank) < ERASABLE_NUM_BANKS => Ok(Expression::Erasable(
+                    W3::from(bank),
+                    W8::from(offset as u16),
+                )),
+                _ => Err(ExpressionError::InvalidLocation(format!(
+                    "E{:o},{:o}",
+                    bank, address
+                ))),
+            };
+        }
+
+        Err(ExpressionError::UnknownName(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{FixedStorage, MemoryWord};
+    use crate::word::{W15, W16};
+
+    fn evaluate(cpu: &Cpu, text: &str) -> u64 {
+        text.parse::<Expression>().unwrap().evaluate(cpu, 3)
+    }
+
+    #[test]
+    fn expression_evaluate() {
+        let mut cpu = Cpu::new(FixedStorage::new());
+        cpu.a = W16::from(0o12345);
+        cpu.z = W16::from(0o2003);
+        cpu.write_erasable(
+            W3::from(3),
+            W8::from(0o12),
+            MemoryWord::with_proper_parity(W15::from(0o777)),
+        );
+        cpu.write_channel(W9::from(0o30), W15::from(0o40));
+
+        assert_eq!(evaluate(&cpu, "a"), 0o12345);
+        assert_eq!(evaluate(&cpu, "A & 77"), 0o45);
+        assert_eq!(evaluate(&cpu, "A & 70 | 7 ^ 1"), 0o46);
+        assert_eq!(evaluate(&cpu, "Z == 2003 && E3,1412 == 777"), 1);
+        assert_eq!(evaluate(&cpu, "E3,12 != 777 || !(CH30 & 40)"), 0);
+        assert_eq!(evaluate(&cpu, "HITS >= 3 && HITS < 4"), 1);
+        assert_eq!(evaluate(&cpu, "(Z > 2003) == 0"), 1);
+    }
+
+    #[test]
+    fn expression_errors() {
+        let parse = |text: &str| text.parse::<Expression>().unwrap_err();
+        assert_eq!(
+            parse("A == 8"),
+            ExpressionError::InvalidNumber("8".to_string())
+        );
+        assert_eq!(parse("X"), ExpressionError::UnknownName("X".to_string()));
+        assert_eq!(parse("A = 1"), ExpressionError::InvalidCharacter('='));
+        assert_eq!(parse("A &&"), ExpressionError::UnexpectedEnd);
+        assert_eq!(
+            parse("(A) 1"),
+            ExpressionError::UnexpectedToken("1".to_string())
+        );
+        assert_eq!(
+            parse("E10,1400"),
+            ExpressionError::InvalidLocation("E10,1400".to_string())
+        );
+        assert_eq!(
+            parse("E1,400"),
+            ExpressionError::InvalidLocation("E1,400".to_string())
+        );
+        assert_eq!(
+            parse("CH1000"),
+            ExpressionError::InvalidLocation("CH1000".to_string())
+        );
+    }
+}
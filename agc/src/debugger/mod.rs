@@ -0,0 +1,595 @@
+mod expression;
+
+use crate::cpu::{self, Cpu, EmulationError, MemoryAccess, TimePulse};
+use crate::history::History;
+use crate::word::{W12, W3, W5};
+use std::fmt;
+
+pub use expression::{Expression, ExpressionError, Operator, Register};
+
+/// Condition stopping the execution before it happens
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Breakpoint {
+    /// Start of the instruction at an address
+    ///
+    /// In switched erasable memory (1400 to 1777), the bank is also
+    /// compared with EBANK when it is given, and in switched fixed memory
+    /// (2000 to 3777) with FBANK.
+    Address {
+        ebank: Option<W3>,
+        fbank: Option<W5>,
+        address: W12,
+    },
+    /// Start of a subinstruction, by name (e.g. `TC0`)
+    Subinstruction(String),
+    /// Execution of a control pulse, by name (e.g. `WOVR`)
+    ControlPulse(String),
+}
+
+impl Breakpoint {
+    /// Returns true if the breakpoint fires at the next control pulse
+    pub fn is_hit(&self, cpu: &Cpu) -> bool {
+        let subinstruction = match cpu.try_current_subinstruction() {
+            Ok(subinstruction) if !cpu.is_standby() => subinstruction,
+            _ => return false,
+        };
+
+        match self {
+            Breakpoint::Address {
+                ebank,
+                fbank,
+                address,
+            } => {
+                cpu.instruction_start() == Some(*address)
+                    && ebank.is_none_or(|ebank| cpu.ebank == ebank)
+                    && fbank.is_none_or(|fbank| cpu.fbank == fbank)
+            }
+            Breakpoint::Subinstruction(name) => {
+                cpu.current_timepulse == TimePulse::T1 && subinstruction.name == name
+            }
+            Breakpoint::ControlPulse(name) => subinstruction
+                .actions(cpu.current_timepulse)
+                .iter()
+                .filter(|action| action.execute(cpu.br))
+                .any(|action| action.control_pulse().name == name),
+        }
+    }
+}
+
+/// Breakpoint which cannot be added
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BreakpointError {
+    /// The bank is given for an address which is not in switched memory
+    UnswitchedAddress(W12),
+    /// No subinstruction has this name
+    UnknownSubinstruction(String),
+    /// No subinstruction executes a control pulse with this name
+    UnknownControlPulse(String),
+}
+
+impl fmt::Display for BreakpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BreakpointError::UnswitchedAddress(address) => {
+                write!(f, "address {} is not switched", address)
+            }
+            BreakpointError::UnknownSubinstruction(name) => {
+                write!(f, "unknown subinstruction {}", name)
+            }
+            BreakpointError::UnknownControlPulse(name) => {
+                write!(f, "unknown control pulse {}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BreakpointError {}
+
+/// Reason why `Debugger::run_until_break` returned
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Stop {
+    /// The breakpoint with this identifier fires at the next control pulse
+    Breakpoint(usize),
+    /// The watchpoint or the watch expression with this identifier fired at
+    /// the last control pulse
+    Watchpoint(usize),
+    /// All the control pulses were executed
+    MaxPulses,
+}
+
+/// Breakpoint, watchpoint or watch expression with its condition
+struct Point<T> {
+    id: usize,
+    trigger: T,
+    condition: Option<Expression>,
+    /// Number of times the trigger fired, whatever the condition
+    hits: u64,
+}
+
+impl<T> Point<T> {
+    fn new(id: usize, trigger: T) -> Self {
+        Self {
+            id,
+            trigger,
+            condition: None,
+            hits: 0,
+        }
+    }
+
+    /// Count a hit of the trigger, returns true if the condition holds
+    fn hit(&mut self, cpu: &Cpu) -> bool {
+        self.hits += 1;
+        let hits = self.hits;
+        self.condition
+            .as_ref()
+            .is_none_or(|condition| condition.is_true(cpu, hits))
+    }
+}
+
+/// Expression watched for changes, with its last value
+struct WatchExpression {
+    expression: Expression,
+    value: u64,
+}
+
+/// Returns the identifier of the first point whose trigger and condition
+/// fire, every trigger is evaluated to count the hits
+fn first_hit<T, F>(points: &mut [Point<T>], cpu: &Cpu, mut triggered: F) -> Option<usize>
+where
+    F: FnMut(&mut T, u64) -> bool,
+{
+    let mut first = None;
+    for point in points {
+        if triggered(&mut point.trigger, point.hits) && point.hit(cpu) && first.is_none() {
+            first = Some(point.id);
+        }
+    }
+    first
+}
+
+/// Breakpoints and watchpoints
+///
+/// Breakpoints stop the execution before what they describe happens.
+/// Watchpoints are erasable memory and channel accesses, they stop the
+/// execution after the control pulse which made the access. Watch
+/// expressions stop it after the control pulse which changed their value.
+/// Each one is given an identifier when it is added, which is reported
+/// when it fires.
+///
+/// Each one can also have a condition, see `Expression`. It then only
+/// fires when the condition is true, but its hits are always counted.
+#[derive(Default)]
+pub struct Debugger {
+    next_id: usize,
+    breakpoints: Vec<Point<Breakpoint>>,
+    watchpoints: Vec<Point<MemoryAccess>>,
+    watch_expressions: Vec<Point<WatchExpression>>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a breakpoint, returns its identifier
+    ///
+    /// Returns an error if the breakpoint can never fire: a bank given for
+    /// an address which is not in the matching switched memory, or an
+    /// unknown name.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<usize, BreakpointError> {
+        match &breakpoint {
+            Breakpoint::Address {
+                ebank,
+                fbank,
+                address,
+            } => {
+                let switched_erasable = (0o1400..0o2000).contains(&address.as_u16());
+                let switched_fixed = (0o2000..0o4000).contains(&address.as_u16());
+                if (ebank.is_some() && !switched_erasable) || (fbank.is_some() && !switched_fixed) {
+                    return Err(BreakpointError::UnswitchedAddress(*address));
+                }
+            }
+            Breakpoint::Subinstruction(name) if !cpu::is_subinstruction_name(name) => {
+                return Err(BreakpointError::UnknownSubinstruction(name.clone()));
+            }
+            Breakpoint::ControlPulse(name) if !cpu::is_control_pulse_name(name) => {
+                return Err(BreakpointError::UnknownControlPulse(name.clone()));
+            }
+            _ => (),
+        }
+
+        let id = self.next_id();
+        self.breakpoints.push(Point::new(id, breakpoint));
+        Ok(id)
+    }
+
+    /// Add a watchpoint, returns its identifier
+    pub fn add_watchpoint(&mut self, access: MemoryAccess) -> usize {
+        let id = self.next_id();
+        self.watchpoints.push(Point::new(id, access));
+        id
+    }
+
+    /// Add an expression stopping the execution when its value changes,
+    /// returns its identifier
+    ///
+    /// The value is compared with the one at the start of each run.
+    pub fn add_watch_expression(&mut self, expression: Expression) -> usize {
+        let id = self.next_id();
+        let watch = WatchExpression {
+            expression,
+            value: 0,
+        };
+        self.watch_expressions.push(Point::new(id, watch));
+        id
+    }
+
+    /// Set or clear the condition of a breakpoint, a watchpoint or a watch
+    /// expression, returns false if it does not exist
+    pub fn set_condition(&mut self, id: usize, condition: Option<Expression>) -> bool {
+        if let Some(point) = self.breakpoints.iter_mut().find(|p| p.id == id) {
+            point.condition = condition;
+        } else if let Some(point) = self.watchpoints.iter_mut().find(|p| p.id == id) {
+            point.condition = condition;
+        } else if let Some(point) = self.watch_expressions.iter_mut().find(|p| p.id == id) {
+            point.condition = condition;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Remove a breakpoint, a watchpoint or a watch expression, returns
+    /// false if it does not exist
+    pub fn remove(&mut self, id: usize) -> bool {
+        let count = self.count();
+        self.breakpoints.retain(|p| p.id != id);
+        self.watchpoints.retain(|p| p.id != id);
+        self.watch_expressions.retain(|p| p.id != id);
+        count != self.count()
+    }
+
+    pub fn breakpoint(&self, id: usize) -> Option<&Breakpoint> {
+        self.breakpoints
+            .iter()
+            .find(|p| p.id == id)
+            .map(|p| &p.trigger)
+    }
+
+    pub fn watchpoint(&self, id: usize) -> Option<MemoryAccess> {
+        self.watchpoints
+            .iter()
+            .find(|p| p.id == id)
+            .map(|p| p.trigger)
+    }
+
+    pub fn watch_expression(&self, id: usize) -> Option<&Expression> {
+        self.watch_expressions
+            .iter()
+            .find(|p| p.id == id)
+            .map(|p| &p.trigger.expression)
+    }
+
+    /// Number of times a breakpoint, a watchpoint or a watch expression was
+    /// reached, even when its condition was false
+    pub fn hits(&self, id: usize) -> Option<u64> {
+        let breakpoints = self.breakpoints.iter().map(|p| (p.id, p.hits));
+        let watchpoints = self.watchpoints.iter().map(|p| (p.id, p.hits));
+        let watch_expressions = self.watch_expressions.iter().map(|p| (p.id, p.hits));
+        breakpoints
+            .chain(watchpoints)
+            .chain(watch_expressions)
+            .find(|(i, _)| *i == id)
+            .map(|(_, hits)| hits)
+    }
+
+    /// Run at most `max_pulses` control pulses, until a breakpoint or a
+    /// watchpoint fires
+    ///
+    /// A breakpoint firing at the current control pulse is ignored, so
+    /// that the execution can continue after it was reported.
+    pub fn run_until_break(
+        &mut self,
+        cpu: &mut Cpu,
+        max_pulses: u64,
+    ) -> Result<Stop, EmulationError> {
+        self.run(cpu, max_pulses, |cpu| cpu.try_step_control_pulse())
+    }
+
+    /// Same as `run_until_break`, but the control pulses are recorded in
+    /// the history
+    pub fn run_until_break_with_history(
+        &mut self,
+        cpu: &mut Cpu,
+        history: &mut History,
+        max_pulses: u64,
+    ) -> Result<Stop, EmulationError> {
+        self.run(cpu, max_pulses, |cpu| history.step_control_pulse(cpu))
+    }
+
+    fn run<F>(
+        &mut self,
+        cpu: &mut Cpu,
+        max_pulses: u64,
+        mut step: F,
+    ) -> Result<Stop, EmulationError>
+    where
+        F: FnMut(&mut Cpu) -> Result<(), EmulationError>,
+    {
+        // The CPU may have been changed since the last run
+        for point in &mut self.watch_expressions {
+            point.trigger.value = point.trigger.expression.evaluate(cpu, point.hits);
+        }
+
+        for _ in 0..max_pulses {
+            step(cpu)?;
+
+            let accesses = cpu.accesses();
+            let watchpoint = first_hit(&mut self.watchpoints, cpu, |access, _| {
+                accesses.contains(access)
+            });
+            let watch_expression = first_hit(&mut self.watch_expressions, cpu, |watch, hits| {
+                let value = watch.expression.evaluate(cpu, hits);
+                let changed = value != watch.value;
+                watch.value = value;
+                changed
+            });
+            if let Some(id) = watchpoint.or(watch_expression) {
+                return Ok(Stop::Watchpoint(id));
+            }
+
+            let breakpoint = first_hit(&mut self.breakpoints, cpu, |breakpoint, _| {
+                breakpoint.is_hit(cpu)
+            });
+            if let Some(id) = breakpoint {
+                return Ok(Stop::Breakpoint(id));
+            }
+        }
+        Ok(Stop::MaxPulses)
+    }
+
+    fn count(&self) -> usize {
+        self.breakpoints.len() + self.watchpoints.len() + self.watch_expressions.len()
+    }
+
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{FixedStorage, MemoryWord};
+    use crate::word::{W10, W15, W16, W3, W6, W8, W9};
+
+    /// CPU adding 5 to 100 in a loop in switched fixed memory, with FBANK 2
+    fn cpu() -> Cpu {
+        let program = [
+            0o12001, // 2000: TCF 2001
+            0o30100, // 2001: CA 100
+            0o62010, // 2002: AD 2010
+            0o54100, // 2003: TS 100
+            0o00006, // 2004: EXTEND
+            0o01030, // 2005: WRITE 30
+            0o12001, // 2006: TCF 2001
+            0o00000, 0o00005, // 2010: 5
+        ];
+        let mut storage = FixedStorage::new();
+        for (offset, word) in program.iter().enumerate() {
+            storage.write(
+                W6::from(2),
+                W10::from(offset as u16),
+                MemoryWord::with_proper_parity(W15::from(*word)),
+            );
+        }
+        let mut cpu = Cpu::new(storage);
+        cpu.fbank = W5::from(2);
+        cpu
+    }
+
+    #[test]
+    fn debugger_address() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        let other_bank = debugger
+            .add_breakpoint(Breakpoint::Address {
+                ebank: None,
+                fbank: Some(W5::from(3)),
+                address: W12::from(0o2003),
+            })
+            .unwrap();
+        let id = debugger
+            .add_breakpoint(Breakpoint::Address {
+                ebank: None,
+                fbank: Some(W5::from(2)),
+                address: W12::from(0o2003),
+            })
+            .unwrap();
+
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Breakpoint(id))
+        );
+        assert_eq!(cpu.current_subsintruction_name(), "TS0");
+        assert_eq!(cpu.a, W16::from(0o5));
+
+        // The breakpoint fires again on the next iteration
+        let z = cpu.z;
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Breakpoint(id))
+        );
+        assert_eq!(cpu.z, z);
+
+        assert!(debugger.remove(id));
+        assert!(!debugger.remove(id));
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::MaxPulses)
+        );
+        assert!(debugger.breakpoint(other_bank).is_some());
+    }
+
+    #[test]
+    fn debugger_names() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        let wch = debugger
+            .add_breakpoint(Breakpoint::ControlPulse("WCH".to_string()))
+            .unwrap();
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Breakpoint(wch))
+        );
+        assert_eq!(cpu.current_subsintruction_name(), "WRITE0");
+        assert_ne!(cpu.current_timepulse, TimePulse::T1);
+
+        let ca = debugger
+            .add_breakpoint(Breakpoint::Subinstruction("CA0".to_string()))
+            .unwrap();
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Breakpoint(ca))
+        );
+        assert_eq!(cpu.current_timepulse, TimePulse::T1);
+        assert_eq!(cpu.instruction_start(), Some(W12::from(0o2001)));
+    }
+
+    #[test]
+    fn debugger_invalid_breakpoints() {
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.add_breakpoint(Breakpoint::Subinstruction("CA1".to_string())),
+            Err(BreakpointError::UnknownSubinstruction("CA1".to_string()))
+        );
+        assert_eq!(
+            debugger.add_breakpoint(Breakpoint::ControlPulse("ZIP".to_string())),
+            Err(BreakpointError::UnknownControlPulse("ZIP".to_string()))
+        );
+        assert_eq!(
+            debugger.add_breakpoint(Breakpoint::Address {
+                ebank: Some(W3::from(1)),
+                fbank: None,
+                address: W12::from(0o2003),
+            }),
+            Err(BreakpointError::UnswitchedAddress(W12::from(0o2003)))
+        );
+        assert_eq!(
+            debugger.add_breakpoint(Breakpoint::Address {
+                ebank: None,
+                fbank: Some(W5::from(2)),
+                address: W12::from(0o4000),
+            }),
+            Err(BreakpointError::UnswitchedAddress(W12::from(0o4000)))
+        );
+        assert_eq!(debugger.count(), 0);
+    }
+
+    #[test]
+    fn debugger_watchpoints() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        let write =
+            debugger.add_watchpoint(MemoryAccess::ErasableWrite(W3::zero(), W8::from(0o100)));
+        let channel = debugger.add_watchpoint(MemoryAccess::ChannelWrite(W9::from(0o30)));
+
+        // TS writes 100, CA only reads it and restores it
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(write))
+        );
+        assert_eq!(cpu.current_timepulse, TimePulse::T11);
+        assert_eq!(
+            cpu.read_erasable(W3::zero(), W8::from(0o100)),
+            MemoryWord::with_proper_parity(W15::from(0o5))
+        );
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(channel))
+        );
+        assert_eq!(cpu.read_channel(W9::from(0o30)), W15::from(0o5));
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(write))
+        );
+        assert_eq!(
+            cpu.read_erasable(W3::zero(), W8::from(0o100)),
+            MemoryWord::with_proper_parity(W15::from(0o12))
+        );
+
+        assert!(debugger.remove(write));
+        let read = debugger.add_watchpoint(MemoryAccess::ErasableRead(W3::zero(), W8::from(0o100)));
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(channel))
+        );
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(read))
+        );
+        assert_eq!(cpu.current_subsintruction_name(), "CA0");
+    }
+
+    #[test]
+    fn debugger_conditions() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        let ts = debugger
+            .add_breakpoint(Breakpoint::Address {
+                ebank: None,
+                fbank: None,
+                address: W12::from(0o2003),
+            })
+            .unwrap();
+        debugger.set_condition(ts, Some("HITS == 3".parse().unwrap()));
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Breakpoint(ts))
+        );
+        assert_eq!(cpu.a, W16::from(0o17));
+
+        debugger.set_condition(ts, Some("A > 30".parse().unwrap()));
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Breakpoint(ts))
+        );
+        assert_eq!(cpu.a, W16::from(0o31));
+        assert_eq!(debugger.hits(ts), Some(5));
+        assert!(debugger.remove(ts));
+
+        let channel = debugger.add_watchpoint(MemoryAccess::ChannelWrite(W9::from(0o30)));
+        debugger.set_condition(channel, Some("CH30 & 17 == 2".parse().unwrap()));
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(channel))
+        );
+        assert_eq!(cpu.read_channel(W9::from(0o30)), W15::from(0o62));
+        assert_eq!(debugger.hits(channel), Some(6));
+        assert!(debugger.remove(channel));
+
+        // Stops when the value changes, in both directions
+        let watch = debugger.add_watch_expression("E0,100 & 4".parse().unwrap());
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(watch))
+        );
+        assert_eq!(
+            cpu.read_erasable(W3::zero(), W8::from(0o100)),
+            MemoryWord::with_proper_parity(W15::from(0o67))
+        );
+        assert_eq!(
+            debugger.run_until_break(&mut cpu, 1000),
+            Ok(Stop::Watchpoint(watch))
+        );
+        assert_eq!(
+            cpu.read_erasable(W3::zero(), W8::from(0o100)),
+            MemoryWord::with_proper_parity(W15::from(0o101))
+        );
+        assert_eq!(
+            debugger.watch_expression(watch).unwrap().evaluate(&cpu, 0),
+            0
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use crate::cpu::counters::{CounterCell, CounterInstruction};
+use crate::cpu::{Cpu, CpuSnapshot, EmulationError, Interrupt, SnapshotError, TimePulse};
+use crate::memory::MemoryWord;
+use crate::word::{W15, W3, W8, W9};
+use std::fmt;
+
+/// Default number of control pulses between two snapshots
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1024;
+/// Default number of snapshots kept before the old ones are thinned out
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 256;
+
+/// Error while going back in the history
+///
+/// The CPU and the history are left at the position where they were
+/// before going back.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HistoryError {
+    /// A snapshot could not be restored, e.g. because the history was
+    /// started with another CPU
+    Snapshot(SnapshotError),
+    /// The emulation stopped while replaying the history
+    Emulation(EmulationError),
+}
+
+impl From<SnapshotError> for HistoryError {
+    fn from(error: SnapshotError) -> Self {
+        HistoryError::Snapshot(error)
+    }
+}
+
+impl From<EmulationError> for HistoryError {
+    fn from(error: EmulationError) -> Self {
+        HistoryError::Emulation(error)
+    }
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryError::Snapshot(error) => write!(f, "cannot restore the history: {}", error),
+            HistoryError::Emulation(error) => write!(f, "cannot replay the history: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+/// External input given to the CPU, which is logged to be replayed
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Input {
+    Interrupt(Interrupt),
+    Counter(CounterCell, CounterInstruction),
+    Channel(W9, W15),
+    Erasable(W3, W8, MemoryWord),
+    PressPro,
+    ReleasePro,
+}
+
+impl Input {
+    fn apply(self, cpu: &mut Cpu) {
+        match self {
+            Input::Interrupt(interrupt) => cpu.request_interrupt(interrupt),
+            Input::Counter(cell, instruction) => cpu.request_counter(cell, instruction),
+            Input::Channel(channel, value) => cpu.write_channel(channel, value),
+            Input::Erasable(bank, address, value) => cpu.write_erasable(bank, address, value),
+            Input::PressPro => cpu.press_pro(),
+            Input::ReleasePro => cpu.release_pro(),
+        }
+    }
+}
+
+/// Execution history, to step backwards
+///
+/// The CPU is stepped through the history, which takes a snapshot every
+/// `interval` control pulses and logs the inputs with the position at which
+/// they were given. Going back restores the closest snapshot and replays
+/// the control pulses and inputs up to the wanted position.
+///
+/// The position is the number of control pulses executed since the history
+/// was created. Inputs given after going back discard the rest of the
+/// history, otherwise stepping forward replays it.
+///
+/// When more than `max_snapshots` snapshots are taken, every other one is
+/// dropped in the older half of the history. Going back far in the history
+/// replays more control pulses, but the memory used stays bounded.
+///
+/// Peripherals are not part of the snapshots, they are ticked again while
+/// replaying. The replay is only exact if they do not depend on their
+/// previous ticks.
+pub struct History {
+    interval: u64,
+    max_snapshots: usize,
+    position: u64,
+    /// Snapshots in order of position, the first one at position 0
+    snapshots: Vec<(u64, CpuSnapshot)>,
+    /// Inputs in order of position
+    inputs: Vec<(u64, Input)>,
+    /// Index of the first input which was not applied yet
+    next_input: usize,
+}
+
+impl History {
+    /// Start the history at the current state of the CPU
+    pub fn new(cpu: &Cpu) -> Self {
+        Self::with_interval(cpu, DEFAULT_SNAPSHOT_INTERVAL)
+    }
+
+    /// Start the history, with a snapshot every `interval` control pulses
+    pub fn with_interval(cpu: &Cpu, interval: u64) -> Self {
+        assert!(interval > 0, "the snapshot interval cannot be zero");
+        Self {
+            interval,
+            max_snapshots: DEFAULT_MAX_SNAPSHOTS,
+            position: 0,
+            snapshots: vec![(0, cpu.snapshot())],
+            inputs: Vec::new(),
+            next_input: 0,
+        }
+    }
+
+    /// Set the number of snapshots kept before the old ones are thinned out
+    pub fn set_max_snapshots(&mut self, max_snapshots: usize) {
+        assert!(max_snapshots >= 2, "at least two snapshots must be kept");
+        self.max_snapshots = max_snapshots;
+        self.thin_snapshots();
+    }
+
+    /// Number of control pulses executed since the start of the history
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Give an input to the CPU at the current position
+    pub fn input(&mut self, cpu: &mut Cpu, input: Input) {
+        // The rest of the history does not happen anymore
+        self.inputs.truncate(self.next_input);
+        let position = self.position;
+        self.snapshots.retain(|(p, _)| *p <= position);
+
+        input.apply(cpu);
+        self.inputs.push((self.position, input));
+        self.next_input += 1;
+    }
+
+    /// Run a single control pulse, see `Cpu::try_step_control_pulse`
+    pub fn step_control_pulse(&mut self, cpu: &mut Cpu) -> Result<(), EmulationError> {
+        while let Some((_, input)) = self
+            .inputs
+            .get(self.next_input)
+            .filter(|(p, _)| *p == self.position)
+        {
+            input.apply(cpu);
+            self.next_input += 1;
+        }
+
+        cpu.try_step_control_pulse()?;
+        self.position += 1;
+
+        let last_snapshot = self.snapshots.last().map_or(0, |(p, _)| *p);
+        if self.position.is_multiple_of(self.interval) && last_snapshot < self.position {
+            self.snapshots.push((self.position, cpu.snapshot()));
+            self.thin_snapshots();
+        }
+
+        Ok(())
+    }
+
+    /// Run a single subinstruction, see `Cpu::try_step_subinstruction`
+    pub fn step_subinstruction(&mut self, cpu: &mut Cpu) -> Result<(), EmulationError> {
+        self.step_control_pulse(cpu)?;
+        while cpu.current_timepulse != TimePulse::T1 {
+            self.step_control_pulse(cpu)?;
+        }
+        Ok(())
+    }
+
+    /// Go back by one control pulse
+    ///
+    /// Returns false at the start of the history.
+    pub fn step_back_control_pulse(&mut self, cpu: &mut Cpu) -> Result<bool, HistoryError> {
+        if self.position == 0 {
+            return Ok(false);
+        }
+        let position = self.position - 1;
+        self.go_back(cpu, |history, cpu| history.rewind(cpu, position))?;
+        Ok(true)
+    }
+
+    /// Go back to the start of the previous subinstruction, or of the
+    /// current one when it is in progress
+    ///
+    /// Returns false if the history does not reach it.
+    pub fn step_back_subinstruction(&mut self, cpu: &mut Cpu) -> Result<bool, HistoryError> {
+        self.run_back_until(cpu, |cpu| cpu.current_timepulse == TimePulse::T1)
+    }
+
+    /// Go back to the last position before `value` changed to its current
+    /// value, e.g. `|cpu| cpu.a` to find the last write to A
+    ///
+    /// Returns false if `value` did not change during the history.
+    pub fn run_back_until_changed<T, F>(
+        &mut self,
+        cpu: &mut Cpu,
+        value: F,
+    ) -> Result<bool, HistoryError>
+    where
+        T: PartialEq,
+        F: Fn(&Cpu) -> T,
+    {
+        let current = value(cpu);
+        self.run_back_until(cpu, |cpu| value(cpu) != current)
+    }
+
+    /// Go back to the last position before the current one at which
+    /// `condition` is true
+    ///
+    /// Returns false and stays at the current position if there is none.
+    pub fn run_back_until<F>(&mut self, cpu: &mut Cpu, condition: F) -> Result<bool, HistoryError>
+    where
+        F: Fn(&Cpu) -> bool,
+    {
+        self.go_back(cpu, |history, cpu| history.search_back(cpu, condition))
+    }
+
+    /// Search for `run_back_until`, which may leave the history anywhere
+    /// on error
+    fn search_back<F>(&mut self, cpu: &mut Cpu, condition: F) -> Result<bool, HistoryError>
+    where
+        F: Fn(&Cpu) -> bool,
+    {
+        let end = self.position;
+
+        // Replay each interval backwards, until one has a match
+        let mut interval_end = end;
+        for index in (0..self.snapshots.len()).rev() {
+            let interval_start = self.snapshots[index].0;
+            if interval_start >= interval_end {
+                continue;
+            }
+
+            self.restore(cpu, index)?;
+            let mut found = None;
+            while self.position < interval_end {
+                if condition(cpu) {
+                    found = Some(self.position);
+                }
+                self.step_control_pulse(cpu)?;
+            }
+
+            if let Some(position) = found {
+                self.rewind(cpu, position)?;
+                return Ok(true);
+            }
+            interval_end = interval_start;
+        }
+
+        self.rewind(cpu, end)?;
+        Ok(false)
+    }
+
+    /// Run `go`, which moves in the history, and come back to the current
+    /// position if it fails
+    fn go_back<T, F>(&mut self, cpu: &mut Cpu, go: F) -> Result<T, HistoryError>
+    where
+        F: FnOnce(&mut Self, &mut Cpu) -> Result<T, HistoryError>,
+    {
+        let start = (self.position, self.next_input, cpu.snapshot());
+        let result = go(self, cpu);
+        if result.is_err() {
+            let (position, next_input, snapshot) = start;
+            cpu.restore(&snapshot)?;
+            self.position = position;
+            self.next_input = next_input;
+        }
+        result
+    }
+
+    /// Go to a position which is already in the history
+    fn rewind(&mut self, cpu: &mut Cpu, position: u64) -> Result<(), HistoryError> {
+        // The first snapshot is at position 0 and is never dropped
+        let index = self
+            .snapshots
+            .iter()
+            .rposition(|(p, _)| *p <= position)
+            .unwrap_or(0);
+        self.restore(cpu, index)?;
+        while self.position < position {
+            self.step_control_pulse(cpu)?;
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, cpu: &mut Cpu, index: usize) -> Result<(), SnapshotError> {
+        let (position, snapshot) = &self.snapshots[index];
+        cpu.restore(snapshot)?;
+        self.position = *position;
+        self.next_input = self.inputs.partition_point(|(p, _)| p < position);
+        Ok(())
+    }
+
+    /// Drop every other snapshot in the older half of the history, except
+    /// the first one, while there are too many of them
+    fn thin_snapshots(&mut self) {
+        while self.snapshots.len() > self.max_snapshots {
+            let older_half = self.snapshots.len().div_ceil(2);
+            let mut index = 0;
+            self.snapshots.retain(|_| {
+                index += 1;
+                index > older_half || index % 2 == 1
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::FixedStorage;
+    use crate::word::{W10, W16, W6};
+
+    /// CPU running a loop incrementing A at 4000
+    fn cpu() -> Cpu {
+        // AD 4003, TCF 4000, +1
+        let program = [0o64003, 0o14000, 0o00000, 0o00001];
+        let mut storage = FixedStorage::new();
+        for (offset, word) in program.iter().enumerate() {
+            storage.write(
+                W6::from(2),
+                W10::from(offset as u16),
+                MemoryWord::with_proper_parity(W15::from(*word)),
+            );
+        }
+        Cpu::new(storage)
+    }
+
+    fn run(history: &mut History, cpu: &mut Cpu, num_control_pulses: u64) {
+        for _ in 0..num_control_pulses {
+            history.step_control_pulse(cpu).unwrap();
+        }
+    }
+
+    #[test]
+    fn history_step_back() {
+        let mut cpu = cpu();
+        let mut history = History::with_interval(&cpu, 10);
+        run(&mut history, &mut cpu, 25);
+        let expected = cpu.snapshot().to_bytes();
+        run(&mut history, &mut cpu, 13);
+
+        for _ in 0..13 {
+            assert!(history.step_back_control_pulse(&mut cpu).unwrap());
+        }
+        assert_eq!(history.position(), 25);
+        assert_eq!(cpu.snapshot().to_bytes(), expected);
+
+        // Back to the start
+        history.rewind(&mut cpu, 0).unwrap();
+        assert!(!history.step_back_control_pulse(&mut cpu).unwrap());
+    }
+
+    #[test]
+    fn history_step_back_subinstruction() {
+        let mut cpu = cpu();
+        let mut history = History::with_interval(&cpu, 7);
+        run(&mut history, &mut cpu, 12 * 5 + 4);
+
+        assert!(history.step_back_subinstruction(&mut cpu).unwrap());
+        assert_eq!(history.position(), 12 * 5);
+        assert!(history.step_back_subinstruction(&mut cpu).unwrap());
+        assert_eq!(history.position(), 12 * 4);
+        assert_eq!(cpu.current_timepulse, TimePulse::T1);
+    }
+
+    #[test]
+    fn history_run_back_until_changed() {
+        let mut cpu = cpu();
+        let mut history = History::with_interval(&cpu, 16);
+        run(&mut history, &mut cpu, 12 * 20);
+        let a = cpu.a;
+
+        assert!(history
+            .run_back_until_changed(&mut cpu, |cpu| cpu.a)
+            .unwrap());
+        assert_ne!(cpu.a, a);
+        history.step_control_pulse(&mut cpu).unwrap();
+        assert_eq!(cpu.a, a);
+
+        // The program counter never held 7777
+        let position = history.position();
+        let found = history
+            .run_back_until(&mut cpu, |cpu| cpu.z == W16::from(0o7777))
+            .unwrap();
+        assert!(!found);
+        assert_eq!(history.position(), position);
+    }
+
+    #[test]
+    fn history_inputs() {
+        let mut cpu = cpu();
+        let mut history = History::with_interval(&cpu, 10);
+        run(&mut history, &mut cpu, 15);
+        let channel = W9::from(0o30);
+        history.input(&mut cpu, Input::Channel(channel, W15::from(0o12345)));
+        run(&mut history, &mut cpu, 20);
+        let expected = cpu.snapshot().to_bytes();
+
+        // The input is replayed
+        history.rewind(&mut cpu, 15).unwrap();
+        assert_eq!(cpu.read_channel(channel), W15::zero());
+        run(&mut history, &mut cpu, 20);
+        assert_eq!(cpu.snapshot().to_bytes(), expected);
+
+        // Another input replaces the rest of the history
+        history.rewind(&mut cpu, 12).unwrap();
+        history.input(&mut cpu, Input::Channel(channel, W15::from(0o54321)));
+        run(&mut history, &mut cpu, 23);
+        history.rewind(&mut cpu, 30).unwrap();
+        assert_eq!(cpu.read_channel(channel), W15::from(0o54321));
+    }
+
+    #[test]
+    fn history_thin_snapshots() {
+        let mut reference = cpu();
+        let mut cpu = cpu();
+        let mut history = History::with_interval(&cpu, 10);
+        history.set_max_snapshots(4);
+        run(&mut history, &mut cpu, 200);
+        assert!(history.snapshots.len() <= 4);
+        assert_eq!(history.snapshots[0].0, 0);
+
+        // Positions between the remaining snapshots are replayed
+        for _ in 0..95 {
+            reference.step_control_pulse();
+        }
+        history.rewind(&mut cpu, 95).unwrap();
+        assert_eq!(cpu.snapshot().to_bytes(), reference.snapshot().to_bytes());
+    }
+
+    #[test]
+    fn history_other_cpu() {
+        let mut cpu = cpu();
+        let mut history = History::with_interval(&cpu, 10);
+        run(&mut history, &mut cpu, 25);
+
+        let mut other = Cpu::new(FixedStorage::new());
+        let expected = other.snapshot().to_bytes();
+        assert!(matches!(
+            history.step_back_control_pulse(&mut other),
+            Err(HistoryError::Snapshot(
+                SnapshotError::FixedStorageMismatch { .. }
+            ))
+        ));
+        assert_eq!(other.snapshot().to_bytes(), expected);
+        assert_eq!(history.position(), 25);
+    }
+
+    #[test]
+    fn history_error_keeps_position() {
+        let mut broken = cpu();
+        let mut cpu = cpu();
+        let mut history = History::with_interval(&cpu, 10);
+        run(&mut history, &mut cpu, 30);
+        let expected = cpu.snapshot().to_bytes();
+
+        // Replaying from the snapshot at 10 fails on an illegal stage
+        broken.st = W3::from(0b101);
+        history.snapshots[1] = (10, broken.snapshot());
+
+        assert!(matches!(
+            history.run_back_until(&mut cpu, |_| false),
+            Err(HistoryError::Emulation(_))
+        ));
+        assert_eq!(history.position(), 30);
+        assert_eq!(cpu.snapshot().to_bytes(), expected);
+    }
+}
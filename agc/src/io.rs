@@ -0,0 +1,135 @@
+use crate::cpu::counters::{CounterCell, CounterInstruction};
+use crate::cpu::Interrupt;
+use crate::word::{W15, W9};
+use std::cell::RefCell;
+use std::ops::{Index, IndexMut};
+use std::rc::Rc;
+
+/// Number of addressable I/O channels
+pub const NUM_CHANNELS: usize = 512;
+
+/// Bank of the 512 addressable I/O channels.
+///
+/// Each channel holds 15 bits. Channels 1 and 2 are also addressable
+/// here, but the CPU maps them to registers L and Q instead.
+#[derive(Clone)]
+pub struct Channels {
+    pub inner: Vec<W15>,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Channels {
+    pub fn new() -> Self {
+        Self {
+            inner: vec![W15::zero(); NUM_CHANNELS],
+        }
+    }
+
+    pub fn read(&self, channel: W9) -> W15 {
+        self[channel]
+    }
+
+    pub fn write(&mut self, channel: W9, value: W15) {
+        self[channel] = value;
+    }
+}
+
+impl Index<W9> for Channels {
+    type Output = W15;
+
+    fn index(&self, index: W9) -> &Self::Output {
+        &self.inner[index.as_u16() as usize]
+    }
+}
+
+impl IndexMut<W9> for Channels {
+    fn index_mut(&mut self, index: W9) -> &mut Self::Output {
+        &mut self.inner[index.as_u16() as usize]
+    }
+}
+
+/// Simulated hardware attached to some I/O channels
+///
+/// Peripherals are attached to the CPU for a range of channels. The
+/// channel instructions (see `SequenceRegister::peripheral_code`) call
+/// the hooks through control pulses RCH and WCH.
+pub trait Peripheral {
+    /// Called when the CPU reads a channel of the peripheral
+    ///
+    /// `stored` is the value held in the channel bank. The returned value
+    /// is the one seen by the CPU.
+    fn read(&mut self, _channel: W9, stored: W15) -> W15 {
+        stored
+    }
+
+    /// Called when the CPU writes a channel of the peripheral
+    ///
+    /// The value is also stored in the channel bank.
+    fn write(&mut self, _channel: W9, _value: W15) {}
+
+    /// Called at the end of every MCT
+    ///
+    /// The peripheral may update any channel, e.g. to provide inputs, and
+    /// raise counter and interrupt requests.
+    fn tick(&mut self, _channels: &mut Channels, _requests: &mut Requests) {}
+}
+
+/// Counter and interrupt requests raised by peripherals
+#[derive(Default)]
+pub struct Requests {
+    pub counters: Vec<(CounterCell, CounterInstruction)>,
+    pub interrupts: Vec<Interrupt>,
+}
+
+impl Requests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request a counter instruction on a counter cell
+    pub fn counter(&mut self, cell: CounterCell, instruction: CounterInstruction) {
+        self.counters.push((cell, instruction));
+    }
+
+    /// Request an interrupt
+    pub fn interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupts.push(interrupt);
+    }
+}
+
+/// Shared peripherals, so that the host can keep a handle on them
+impl<P: Peripheral> Peripheral for Rc<RefCell<P>> {
+    fn read(&mut self, channel: W9, stored: W15) -> W15 {
+        self.borrow_mut().read(channel, stored)
+    }
+
+    fn write(&mut self, channel: W9, value: W15) {
+        self.borrow_mut().write(channel, value)
+    }
+
+    fn tick(&mut self, channels: &mut Channels, requests: &mut Requests) {
+        self.borrow_mut().tick(channels, requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_read_write() {
+        let mut channels = Channels::new();
+        assert_eq!(channels.read(W9::from(0o777)), W15::zero());
+
+        channels.write(W9::from(0o30), W15::from(0o12345));
+        channels.write(W9::from(0o777), W15::from(0o77777));
+        assert_eq!(channels.read(W9::from(0o30)), W15::from(0o12345));
+        assert_eq!(channels[W9::from(0o777)], W15::from(0o77777));
+        assert_eq!(channels.read(W9::from(0o31)), W15::zero());
+    }
+}
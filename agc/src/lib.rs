@@ -1,3 +1,6 @@
 pub mod cpu;
+pub mod debugger;
+pub mod history;
+pub mod io;
 pub mod memory;
 pub mod word;
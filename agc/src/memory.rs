@@ -22,11 +22,11 @@ impl MemoryWord {
     }
 
     pub fn with_proper_parity(word: W15) -> Self {
-        Self::new(word, word.count_ones() % 2 == 0)
+        Self::new(word, word.count_ones().is_multiple_of(2))
     }
 
     pub fn with_wrong_parity(word: W15) -> Self {
-        Self::new(word, word.count_ones() % 2 != 0)
+        Self::new(word, !word.count_ones().is_multiple_of(2))
     }
 
     pub fn value(&self) -> W15 {
@@ -38,7 +38,7 @@ impl MemoryWord {
     }
 
     pub fn is_valid(&self) -> bool {
-        self.inner.count_ones() % 2 != 0
+        !self.inner.count_ones().is_multiple_of(2)
     }
 
     pub fn as_register_value(&self) -> W16 {
@@ -46,6 +46,17 @@ impl MemoryWord {
         result.set(15, result.get(14));
         result
     }
+
+    /// Create a memory word from a 16-bit register value
+    ///
+    /// Bit 16 of the register is the sign and is stored in bit 15 of the
+    /// memory word. Bit 15 of the register (the overflow bit) is dropped.
+    /// The parity bit is properly set.
+    pub fn from_register_value(value: W16) -> Self {
+        let mut word = W15::from(value);
+        word.set(14, value.get(15));
+        Self::with_proper_parity(word)
+    }
 }
 
 impl fmt::Display for MemoryWord {
@@ -69,10 +80,17 @@ pub const ERASABLE_BANK_SIZE: usize = 256;
 /// Number of erasable memory banks
 pub const ERASABLE_NUM_BANKS: usize = 8;
 
+#[derive(Clone)]
 pub struct ErasableStorageBank {
     pub inner: Vec<MemoryWord>,
 }
 
+impl Default for ErasableStorageBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ErasableStorageBank {
     pub fn new() -> Self {
         Self {
@@ -104,10 +122,17 @@ impl IndexMut<W8> for ErasableStorageBank {
 }
 
 /// Fixed storage is made of 8 banks of 256 words of read-write memory.
+#[derive(Clone)]
 pub struct ErasableStorage {
     pub banks: Vec<ErasableStorageBank>,
 }
 
+impl Default for ErasableStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ErasableStorage {
     pub fn new() -> Self {
         Self {
@@ -149,13 +174,19 @@ impl IndexMut<W3> for ErasableStorage {
 
 /// Size of each fixed memory bank (in words)
 pub const FIXED_BANK_SIZE: usize = 1024;
-/// Number of fixed memory banks
+/// Number of fixed memory banks (octal 0 to 43, banks 40 to 43 are superbanks)
 pub const FIXED_NUM_BANKS: usize = 36;
 
 pub struct FixedStorageBank {
     pub inner: Vec<MemoryWord>,
 }
 
+impl Default for FixedStorageBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FixedStorageBank {
     pub fn new() -> Self {
         Self {
@@ -191,6 +222,12 @@ pub struct FixedStorage {
     pub banks: Vec<FixedStorageBank>,
 }
 
+impl Default for FixedStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FixedStorage {
     pub fn new() -> Self {
         Self {
@@ -242,6 +279,25 @@ impl FixedStorage {
     pub fn write(&mut self, bank: W6, address: W10, value: MemoryWord) {
         self[bank][address] = value
     }
+
+    /// 64-bit FNV-1a hash of all the words, parity included
+    ///
+    /// This identifies the rope, e.g. to check that a snapshot is restored
+    /// with the program it was taken from. Unlike `std::hash`, the value is
+    /// stable across Rust versions, so it can be stored in files.
+    pub fn hash(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let mut hash = FNV_OFFSET_BASIS;
+        for word in self.banks.iter().flat_map(|bank| bank.inner.iter()) {
+            let raw = word.value().as_u16() | ((word.parity() as u16) << 15);
+            for byte in raw.to_le_bytes().iter() {
+                hash = (hash ^ *byte as u64).wrapping_mul(FNV_PRIME);
+            }
+        }
+        hash
+    }
 }
 
 impl Index<W6> for FixedStorage {
@@ -312,25 +368,60 @@ mod tests {
     fn memoryword_parity() {
         let word = MemoryWord::with_proper_parity(W15::from(0o12346));
         assert_eq!(word.value(), W15::from(0o12346));
-        assert_eq!(word.parity(), false);
+        assert!(!word.parity());
         assert!(word.is_valid());
 
         let word = MemoryWord::with_wrong_parity(W15::from(0o12346));
         assert_eq!(word.value(), W15::from(0o12346));
-        assert_eq!(word.parity(), true);
+        assert!(word.parity());
         assert!(!word.is_valid());
 
         let word = MemoryWord::with_proper_parity(W15::from(0o12347));
         assert_eq!(word.value(), W15::from(0o12347));
-        assert_eq!(word.parity(), true);
+        assert!(word.parity());
         assert!(word.is_valid());
 
         let word = MemoryWord::with_wrong_parity(W15::from(0o12347));
         assert_eq!(word.value(), W15::from(0o12347));
-        assert_eq!(word.parity(), false);
+        assert!(!word.parity());
         assert!(!word.is_valid());
     }
 
+    #[test]
+    fn memoryword_register_value() {
+        let word = MemoryWord::with_proper_parity(W15::from(0o12346));
+        assert_eq!(word.as_register_value(), W16::from(0o012346));
+        assert_eq!(MemoryWord::from_register_value(W16::from(0o012346)), word);
+
+        let word = MemoryWord::with_proper_parity(W15::from(0o52346));
+        assert_eq!(word.as_register_value(), W16::from(0o152346));
+        assert_eq!(MemoryWord::from_register_value(W16::from(0o152346)), word);
+
+        // Overflow is dropped, bit 16 is the sign
+        assert_eq!(
+            MemoryWord::from_register_value(W16::from(0o052346)),
+            MemoryWord::with_proper_parity(W15::from(0o12346))
+        );
+        assert_eq!(
+            MemoryWord::from_register_value(W16::from(0o112346)),
+            MemoryWord::with_proper_parity(W15::from(0o52346))
+        );
+    }
+
+    #[test]
+    fn fixed_storage_hash() {
+        let mut storage = FixedStorage::new();
+        let empty = storage.hash();
+        assert_eq!(empty, FixedStorage::new().hash());
+
+        storage.write(
+            W6::from(0o43),
+            W10::from(0o1777),
+            MemoryWord::with_proper_parity(W15::from(0o1)),
+        );
+        assert_ne!(storage.hash(), empty);
+    }
+
     #[test]
     fn memoryword_display() {
         let word = MemoryWord::with_proper_parity(W15::from(0o12346));
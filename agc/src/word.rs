@@ -116,9 +116,9 @@ impl<WS: WordSize> From<u16> for Word<WS> {
     }
 }
 
-impl<WS: WordSize> Into<u16> for Word<WS> {
-    fn into(self) -> u16 {
-        self.inner
+impl<WS: WordSize> From<Word<WS>> for u16 {
+    fn from(from: Word<WS>) -> u16 {
+        from.inner
     }
 }
 
@@ -621,10 +621,10 @@ mod tests {
     fn get_set() {
         let mut w10 = W10::from(0);
 
-        assert_eq!(w10.get(5), false);
-        assert_eq!(w10.set(5, true), false);
-        assert_eq!(w10.get(5), true);
-        assert_eq!(w10.set(5, true), true);
+        assert!(!w10.get(5));
+        assert!(!w10.set(5, true));
+        assert!(w10.get(5));
+        assert!(w10.set(5, true));
         assert_eq!(w10.as_u16(), 0b00_0010_0000);
 
         assert!(catch_unwind(|| w10.get(10)).is_err());
@@ -656,7 +656,7 @@ mod tests {
         assert_eq!(w10 & W10::zero(), W10::zero());
         assert_eq!(w10 & W10::full(), w10);
 
-        assert_eq!(w10 & 0b0000_0011u8, W10::from(0b00_000_0010));
+        assert_eq!(w10 & 0b0000_0011u8, W10::from(0b00_0000_0010));
         assert_eq!(w10 & 0b1111_0000_1111_0000u16, W10::from(0b00_0110_0000));
         assert_eq!(w10 & W10::from(0b11_1111_0000), W10::from(0b00_0110_0000));
         assert_eq!(
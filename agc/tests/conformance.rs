@@ -10,15 +10,21 @@
 //!
 //! The `test_agc.v` file is the Verilog file I used to create the
 //! `verilog_sim.csv` file with the `agc_simulation` package.
+//!
+//! The `traces` directory holds traces of short programs in the same
+//! format, for instructions which Aurora 12 does not run within the
+//! Verilog simulation. They are NOT Verilog simulations: they were recorded
+//! from this emulator with `record_traces`, after checking the result of
+//! each instruction, and only guard the timing of these instructions
+//! against regressions.
 
+use std::fmt::Write;
 use std::path::PathBuf;
 
 use agc::cpu::Cpu;
-use agc::memory::load_yayul_img_file;
+use agc::memory::{load_yayul_img_file, FixedStorage, MemoryWord};
 use agc::word::*;
 
-const NUM_SUBINSTRUCTIONS: usize = 100;
-
 #[test]
 fn conformance() {
     // Setup emulator
@@ -32,19 +38,128 @@ fn conformance() {
     // Load Verilog simulation data
     let verilog_sim = include_str!("verilog_sim.csv");
 
-    for (line_num, line) in verilog_sim
+    let num_subinstructions = check_trace(&mut cpu, verilog_sim);
+    assert!(num_subinstructions >= 100, "truncated Verilog simulation");
+}
+
+/// AD and MASK, with an overflow in A
+const AD_MASK: [u16; 9] = [
+    0o34100, // 4000: CA 4100
+    0o64101, // 4001: AD 4101
+    0o64102, // 4002: AD 4102
+    0o74103, // 4003: MASK 4103
+    0o54050, // 4004: TS 50
+    0o34102, // 4005: CA 4102
+    0o60050, // 4006: AD 50
+    0o70050, // 4007: MASK 50
+    0o14010, // 4010: TCF 4010
+];
+const AD_MASK_DATA: [u16; 4] = [0o00025, 0o37777, 0o77776, 0o12345];
+
+#[test]
+fn trace_ad_mask() {
+    let mut cpu = cpu_with_program(&AD_MASK, &AD_MASK_DATA);
+    check_trace(&mut cpu, include_str!("traces/ad_mask.csv"));
+
+    // 25 + 37777 overflows to 40024, -1 brings it to 40023 and MASK 12345
+    // leaves 00001, which TS stores. -1 + 1 is -0, masked to 00001 again.
+    assert_eq!(
+        cpu.read_erasable(W3::zero(), W8::from(0o50)).value(),
+        W15::from(0o00001)
+    );
+    assert_eq!(cpu.a, W16::from(0o000001));
+    assert_eq!(cpu.z, W16::from(0o4011));
+}
+
+/// LXCH, DAS, ADS, then an indexed DXCH
+const DOUBLE: [u16; 16] = [
+    0o34100, // 4000: CA 4100
+    0o54060, // 4001: TS 60
+    0o34101, // 4002: CA 4101
+    0o54061, // 4003: TS 61
+    0o34102, // 4004: CA 4102
+    0o54062, // 4005: TS 62
+    0o22062, // 4006: LXCH 62
+    0o34103, // 4007: CA 4103
+    0o20061, // 4010: DAS 60
+    0o34103, // 4011: CA 4103
+    0o26061, // 4012: ADS 61
+    0o34104, // 4013: CA 4104
+    0o54063, // 4014: TS 63
+    0o50063, // 4015: INDEX 63
+    0o52061, // 4016: DXCH 60
+    0o14017, // 4017: TCF 4017
+];
+const DOUBLE_DATA: [u16; 5] = [0o00012, 0o00003, 0o00100, 0o00005, 0o00002];
+
+#[test]
+fn trace_double() {
+    let mut cpu = cpu_with_program(&DOUBLE, &DOUBLE_DATA);
+    check_trace(&mut cpu, include_str!("traces/double.csv"));
+
+    // LXCH loads 100 into L, DAS adds 5,100 to 12,3 and ADS adds 5 to the
+    // lower word. DXCH, indexed by 2, then exchanges 2,0 with 0,2 in 62,63.
+    let erasable = |address: u16| cpu.read_erasable(W3::zero(), W8::from(address)).value();
+    assert_eq!(erasable(0o60), W15::from(0o00017));
+    assert_eq!(erasable(0o61), W15::from(0o00110));
+    assert_eq!(erasable(0o62), W15::from(0o00002));
+    assert_eq!(erasable(0o63), W15::from(0o00000));
+    assert_eq!(cpu.a, W16::from(0o000000));
+    assert_eq!(cpu.l, W16::from(0o000002));
+    assert_eq!(cpu.z, W16::from(0o4020));
+}
+
+/// Record the traces of the programs, run with `cargo test -- --ignored`
+#[test]
+#[ignore]
+fn record_traces() {
+    let traces = [
+        ("ad_mask.csv", &AD_MASK[..], &AD_MASK_DATA[..], 20),
+        ("double.csv", &DOUBLE[..], &DOUBLE_DATA[..], 40),
+    ];
+    for (name, program, data, num_subinstructions) in traces.iter() {
+        let mut cpu = cpu_with_program(program, data);
+        let trace = record_trace(&mut cpu, *num_subinstructions);
+        let mut filepath = PathBuf::from("tests");
+        filepath.push("traces");
+        filepath.push(name);
+        std::fs::write(filepath, trace).unwrap();
+    }
+}
+
+/// CPU with a program at 4000 and its data at 4100
+fn cpu_with_program(program: &[u16], data: &[u16]) -> Cpu {
+    let mut storage = FixedStorage::new();
+    let words = program.iter().enumerate();
+    let data = data.iter().enumerate().map(|(i, word)| (0o100 + i, word));
+    for (offset, word) in words.chain(data) {
+        storage.write(
+            W6::from(2),
+            W10::from(offset as u16),
+            MemoryWord::with_proper_parity(W15::from(*word)),
+        );
+    }
+    Cpu::new(storage)
+}
+
+/// Compare the registers with the trace at each time pulse, returns the
+/// number of subinstructions checked
+fn check_trace(cpu: &mut Cpu, trace: &str) -> usize {
+    let num_subinstructions = (trace.lines().count() - 1) / 12;
+
+    for (line_num, line) in trace
         .lines()
         .enumerate()
         .skip(1)
-        .take(NUM_SUBINSTRUCTIONS * 12)
+        .take(num_subinstructions * 12)
     {
         let line_num = line_num + 1;
 
-        // Read register status from Verilog file
+        // Read register status from the trace
         let expected_registers = RegisterStatus::parse(line, line_num);
 
         // Compare the registers
-        let actual_registers = RegisterStatus::from_cpu(&cpu);
+        let actual_registers = RegisterStatus::from_cpu(cpu);
         assert_eq!(
             actual_registers, expected_registers,
             "different registers at line {}",
@@ -54,6 +169,29 @@ fn conformance() {
         // Step the simulation
         cpu.step_control_pulse();
     }
+
+    num_subinstructions
+}
+
+/// Trace of the registers at each time pulse, in the format of the
+/// Verilog simulation
+fn record_trace(cpu: &mut Cpu, num_subinstructions: usize) -> String {
+    let mut trace =
+        String::from("Subinstruction Count;Time Pulse;A;L;Q;Z;EBANK;FBANK;B;G;S;SQ;ST;X;Y;BR;\n");
+    for count in 0..num_subinstructions {
+        for t in 1..=12 {
+            let r = RegisterStatus::from_cpu(cpu);
+            writeln!(
+                trace,
+                "{};T{:02};{:06o};{:06o};{:06o};{:06o};{:o};{:02o};{:06o};{:06o};{:04o};{:03o};{:o};{:06o};{:06o};{:o};",
+                count, t, r.a.as_u16(), r.l.as_u16(), r.q.as_u16(), r.z.as_u16(), r.ebank.as_u16(), r.fbank.as_u16(),
+                r.b.as_u16(), r.g.as_u16(), r.s.as_u16(), r.sq.as_u16(), r.st.as_u16(), r.x.as_u16(), r.y.as_u16(), r.br.as_u16()
+            )
+            .unwrap();
+            cpu.step_control_pulse();
+        }
+    }
+    trace
 }
 
 #[derive(Debug, PartialEq)]
@@ -81,10 +219,9 @@ impl RegisterStatus {
     fn parse(line: &str, line_num: usize) -> Self {
         let parse_octal = |src: Option<&str>, register_name: &str| -> u16 {
             match src {
-                Some(value) => u16::from_str_radix(value, 8).expect(&format!(
-                    "invalid \"{}\" value at line {}",
-                    register_name, line_num
-                )),
+                Some(value) => u16::from_str_radix(value, 8).unwrap_or_else(|_| {
+                    panic!("invalid \"{}\" value at line {}", register_name, line_num)
+                }),
                 None => panic!("missing \"{}\" value", register_name),
             }
         };
@@ -143,7 +280,7 @@ impl RegisterStatus {
             b: cpu.b,
             g: cpu.g,
             s: cpu.s.inner(),
-            sq: cpu.sq.inner().into(),
+            sq: cpu.sq.inner(),
             st: cpu.st,
             x: cpu.x,
             y: cpu.y,
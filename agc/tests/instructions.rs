@@ -0,0 +1,2422 @@
+//! Instruction-level tests
+//!
+//! Each test assembles a small program by hand, places it at the start
+//! address (octal 4000) of an otherwise empty fixed storage and runs the
+//! emulator from GOJAM. Programs end with a `TCF` to themselves, so
+//! running a few extra subinstructions does not change the results.
+//!
+//! Constants used by the programs are stored at octal 4400.
+
+use agc::cpu::counters::{self, CounterCell, CounterInstruction};
+use agc::cpu::interrupts::{ARUPT, BBRUPT, BRUPT, LRUPT, QRUPT, ZRUPT};
+use agc::cpu::{
+    Alarm, Cpu, CpuSnapshot, EmulationError, Interrupt, MemoryAccess, SnapshotError, TimePulse,
+};
+use agc::io::{Channels, Peripheral, Requests};
+use agc::memory::{FixedStorage, MemoryWord};
+use agc::word::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Address of the first constant
+const DATA: u16 = 0o4400;
+
+fn cpu_with_program(program: &[u16], data: &[u16]) -> Cpu {
+    let mut storage = FixedStorage::new();
+
+    // The program starts at 4000 and continues in fixed-fixed bank 3
+    for (offset, word) in program.iter().enumerate() {
+        let offset = offset as u16;
+        storage.write(
+            W6::from(2 + offset / 0o2000),
+            W10::from(offset % 0o2000),
+            MemoryWord::with_proper_parity(W15::from(*word)),
+        );
+    }
+    for (offset, word) in data.iter().enumerate() {
+        storage.write(
+            W6::from(2),
+            W10::from(DATA - 0o4000 + offset as u16),
+            MemoryWord::with_proper_parity(W15::from(*word)),
+        );
+    }
+
+    Cpu::new(storage)
+}
+
+fn run(cpu: &mut Cpu, num_subinstructions: usize) {
+    for _ in 0..num_subinstructions {
+        cpu.step_subinstruction();
+    }
+}
+
+fn erasable(cpu: &Cpu, address: u16) -> W15 {
+    cpu.read_erasable(W3::zero(), W8::from(address)).value()
+}
+
+/// Instruction encoding helpers
+mod asm {
+    pub fn ccs(k: u16) -> u16 {
+        0o10000 | k
+    }
+    pub fn ca(k: u16) -> u16 {
+        0o30000 | k
+    }
+    pub fn ad(k: u16) -> u16 {
+        0o60000 | k
+    }
+    pub fn mask(k: u16) -> u16 {
+        0o70000 | k
+    }
+    pub fn ts(k: u16) -> u16 {
+        0o54000 | k
+    }
+    pub fn tcf(k: u16) -> u16 {
+        0o10000 | k
+    }
+    pub fn das(k: u16) -> u16 {
+        0o20000 | (k + 1)
+    }
+    pub fn lxch(k: u16) -> u16 {
+        0o22000 | k
+    }
+    pub fn ads(k: u16) -> u16 {
+        0o26000 | k
+    }
+    pub fn cs(k: u16) -> u16 {
+        0o40000 | k
+    }
+    pub fn index(k: u16) -> u16 {
+        0o50000 | k
+    }
+    pub fn resume() -> u16 {
+        index(0o17)
+    }
+    pub fn incr(k: u16) -> u16 {
+        0o24000 | k
+    }
+    pub fn edrupt(k: u16) -> u16 {
+        0o07000 | k
+    }
+    pub fn dxch(k: u16) -> u16 {
+        0o52000 | (k + 1)
+    }
+    pub fn dv(k: u16) -> u16 {
+        0o10000 | k
+    }
+    pub fn extend() -> u16 {
+        0o00006
+    }
+    pub fn inhint() -> u16 {
+        0o00004
+    }
+    pub fn mp(k: u16) -> u16 {
+        0o70000 | k
+    }
+    pub fn bzf(k: u16) -> u16 {
+        0o10000 | k
+    }
+    pub fn msu(k: u16) -> u16 {
+        0o20000 | k
+    }
+    pub fn qxch(k: u16) -> u16 {
+        0o22000 | k
+    }
+    pub fn aug(k: u16) -> u16 {
+        0o24000 | k
+    }
+    pub fn dim(k: u16) -> u16 {
+        0o26000 | k
+    }
+    pub fn dca(k: u16) -> u16 {
+        0o30000 | (k + 1)
+    }
+    pub fn dcs(k: u16) -> u16 {
+        0o40000 | (k + 1)
+    }
+    pub fn su(k: u16) -> u16 {
+        0o60000 | k
+    }
+    pub fn bzmf(k: u16) -> u16 {
+        0o60000 | k
+    }
+    pub fn read(k: u16) -> u16 {
+        k
+    }
+    pub fn write(k: u16) -> u16 {
+        0o01000 | k
+    }
+    pub fn rand(k: u16) -> u16 {
+        0o02000 | k
+    }
+    pub fn wand(k: u16) -> u16 {
+        0o03000 | k
+    }
+    pub fn ror(k: u16) -> u16 {
+        0o04000 | k
+    }
+    pub fn wor(k: u16) -> u16 {
+        0o05000 | k
+    }
+    pub fn rxor(k: u16) -> u16 {
+        0o06000 | k
+    }
+}
+
+fn add(lhs: u16, rhs: u16) -> W16 {
+    let mut cpu = cpu_with_program(
+        &[asm::ca(DATA), asm::ad(DATA + 1), asm::tcf(0o4002)],
+        &[lhs, rhs],
+    );
+    run(&mut cpu, 20);
+    cpu.a
+}
+
+#[test]
+fn ad() {
+    assert_eq!(add(0o00005, 0o00003), W16::from(0o000010));
+    assert_eq!(add(0o00005, 0o77774), W16::from(0o000002));
+    assert_eq!(add(0o77772, 0o77774), W16::from(0o177767));
+    assert_eq!(add(0o77777, 0o00000), W16::from(0o177777));
+    assert_eq!(add(0o00000, 0o00000), W16::from(0o000000));
+}
+
+#[test]
+fn ad_overflow() {
+    // Positive overflow
+    assert_eq!(add(0o37777, 0o00001), W16::from(0o040000));
+    // Negative overflow
+    assert_eq!(add(0o40000, 0o77776), W16::from(0o137777));
+}
+
+#[test]
+fn ad_keeps_memory() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ad(DATA),
+            asm::ad(DATA),
+            asm::tcf(0o4003),
+        ],
+        &[0o00007],
+    );
+    run(&mut cpu, 20);
+    assert_eq!(cpu.a, W16::from(0o000025));
+}
+
+#[test]
+fn mask() {
+    let mut cpu = cpu_with_program(
+        &[asm::ca(DATA), asm::mask(DATA + 1), asm::tcf(0o4002)],
+        &[0o12345, 0o07070],
+    );
+    run(&mut cpu, 20);
+    assert_eq!(cpu.a, W16::from(0o002040));
+
+    let mut cpu = cpu_with_program(
+        &[asm::ca(DATA), asm::mask(DATA + 1), asm::tcf(0o4002)],
+        &[0o77777, 0o52525],
+    );
+    run(&mut cpu, 20);
+    assert_eq!(cpu.a, W16::from(0o152525));
+}
+
+#[test]
+fn ad_mask_erasable() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ca(DATA + 1),
+            asm::ad(0o100),
+            asm::ad(0o100),
+            asm::mask(0o100),
+            asm::tcf(0o4006),
+        ],
+        &[0o00017, 0o00001],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o000017));
+}
+
+/// Run CCS on `value` and return the resulting A and the number of
+/// skipped instructions
+fn compare_and_skip(value: u16) -> (W16, u16) {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ccs(0o100),
+            asm::tcf(0o4003),
+            asm::tcf(0o4004),
+            asm::tcf(0o4005),
+            asm::tcf(0o4006),
+        ],
+        &[value],
+    );
+    run(&mut cpu, 30);
+    (cpu.a, cpu.z.as_u16() - 0o4004)
+}
+
+#[test]
+fn ccs() {
+    assert_eq!(compare_and_skip(0o00005), (W16::from(0o000004), 0));
+    assert_eq!(compare_and_skip(0o00001), (W16::from(0o000000), 0));
+    assert_eq!(compare_and_skip(0o37777), (W16::from(0o037776), 0));
+    assert_eq!(compare_and_skip(0o00000), (W16::from(0o000000), 1));
+    assert_eq!(compare_and_skip(0o77772), (W16::from(0o000004), 2));
+    assert_eq!(compare_and_skip(0o77776), (W16::from(0o000000), 2));
+    assert_eq!(compare_and_skip(0o40000), (W16::from(0o037776), 2));
+    assert_eq!(compare_and_skip(0o77777), (W16::from(0o000000), 3));
+}
+
+#[test]
+fn ccs_keeps_memory() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ccs(0o100),
+            asm::ccs(0o100),
+            asm::ccs(0o100),
+            asm::tcf(0o4005),
+        ],
+        &[0o00003],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o000002));
+}
+
+#[test]
+fn ccs_a() {
+    // CCS A with overflow uses bit 16 of A as the sign
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ad(DATA),
+            asm::ccs(0o0),
+            asm::tcf(0o4003),
+            asm::tcf(0o4004),
+        ],
+        &[0o20000],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o037777));
+    assert_eq!(cpu.z, W16::from(0o4004));
+}
+
+#[test]
+fn ts_overflow_skips() {
+    // Positive overflow: A is set to +1 and the next instruction is skipped
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ad(DATA),
+            asm::ts(0o100),
+            asm::tcf(0o4003),
+            asm::tcf(0o4004),
+        ],
+        &[0o20000],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o000001));
+    assert_eq!(cpu.z, W16::from(0o4005));
+
+    // Negative overflow: A is set to -1 and the next instruction is skipped
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ad(DATA),
+            asm::ts(0o100),
+            asm::tcf(0o4003),
+            asm::tcf(0o4004),
+        ],
+        &[0o57777],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o177776));
+    assert_eq!(cpu.z, W16::from(0o4005));
+}
+
+#[test]
+fn lxch() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::lxch(0o100),
+            asm::ca(0o100),
+            asm::tcf(0o4004),
+        ],
+        &[0o12345],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.l, W16::from(0o012345));
+    assert_eq!(cpu.a, W16::from(0o000000));
+}
+
+#[test]
+fn ads() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ca(DATA + 1),
+            asm::ads(0o100),
+            asm::ca(DATA),
+            asm::ad(0o100),
+            asm::tcf(0o4006),
+        ],
+        &[0o00003, 0o00004],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o000012));
+}
+
+/// Run DAS with `acc` in A,L and `mem` in erasable 100,101. Returns A,L
+/// after the DAS, or the contents of 100,101 if `exchange` is set.
+fn double_add(acc: (u16, u16), mem: (u16, u16), exchange: bool) -> (W16, W16) {
+    let mut program = vec![
+        asm::ca(DATA + 3),
+        asm::ts(0o101),
+        asm::ca(DATA + 2),
+        asm::ts(0o100),
+        asm::ca(DATA + 1),
+        asm::ts(0o102),
+        asm::lxch(0o102),
+        asm::ca(DATA),
+        asm::das(0o100),
+    ];
+    if exchange {
+        program.push(asm::dxch(0o100));
+    }
+    program.push(asm::tcf(0o4000 + program.len() as u16));
+
+    let mut cpu = cpu_with_program(&program, &[acc.0, acc.1, mem.0, mem.1]);
+    run(&mut cpu, 40);
+    (cpu.a, cpu.l)
+}
+
+#[test]
+fn das() {
+    let cases = [
+        // No carry
+        ((0o00001, 0o10000), (0o00002, 0o20000), (0o00003, 0o30000)),
+        // Positive carry from the lower word
+        ((0o00001, 0o30000), (0o00002, 0o20000), (0o00004, 0o10000)),
+        // Negative carry from the lower word
+        ((0o77776, 0o47777), (0o77775, 0o57777), (0o177773, 0o167777)),
+        // Overflow of the upper word
+        ((0o30000, 0o00000), (0o20000, 0o00000), (0o10000, 0o00000)),
+        ((0o47777, 0o00000), (0o57777, 0o00000), (0o167777, 0o00000)),
+    ];
+
+    for (acc, mem, result) in cases.iter() {
+        let (upper, lower) = double_add(*acc, *mem, true);
+        assert_eq!((upper, lower), (W16::from(result.0), W16::from(result.1)));
+    }
+
+    // A holds the overflow of the upper word, L is cleared
+    assert_eq!(
+        double_add((0o00001, 0o30000), (0o00002, 0o20000), false),
+        (W16::from(0o000000), W16::from(0o000000))
+    );
+    assert_eq!(
+        double_add((0o30000, 0o00000), (0o20000, 0o00000), false),
+        (W16::from(0o000001), W16::from(0o000000))
+    );
+    assert_eq!(
+        double_add((0o47777, 0o00000), (0o57777, 0o00000), false),
+        (W16::from(0o177776), W16::from(0o000000))
+    );
+}
+
+#[test]
+fn ddoubl() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA + 1),
+            asm::ts(0o100),
+            asm::lxch(0o100),
+            asm::ca(DATA),
+            asm::das(0o0),
+            asm::tcf(0o4005),
+        ],
+        &[0o00001, 0o10000],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o000002));
+    assert_eq!(cpu.l, W16::from(0o020000));
+}
+
+#[test]
+fn dxch() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA + 3),
+            asm::ts(0o101),
+            asm::ca(DATA + 2),
+            asm::ts(0o100),
+            asm::ca(DATA + 1),
+            asm::ts(0o102),
+            asm::lxch(0o102),
+            asm::ca(DATA),
+            asm::dxch(0o100),
+            asm::dxch(0o100),
+            asm::dxch(0o100),
+            asm::tcf(0o4013),
+        ],
+        &[0o00001, 0o00002, 0o00003, 0o00004],
+    );
+    run(&mut cpu, 40);
+    assert_eq!(cpu.a, W16::from(0o000003));
+    assert_eq!(cpu.l, W16::from(0o000004));
+}
+
+#[test]
+fn dtcb() {
+    // DXCH Z jumps to the address in A with the bank in L, and returns the
+    // previous Z and BB in A,L
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA + 1),
+            asm::ts(0o100),
+            asm::lxch(0o100),
+            asm::ca(DATA),
+            asm::dxch(0o5),
+            asm::tcf(0o4005),
+            asm::tcf(0o4006),
+        ],
+        &[0o04006, 0o00000],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.z, W16::from(0o4007));
+    assert_eq!(cpu.a, W16::from(0o004005));
+    assert_eq!(cpu.l, W16::from(0o000000));
+}
+
+#[test]
+fn index() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::index(0o100),
+            asm::ca(DATA + 1),
+            asm::tcf(0o4004),
+        ],
+        &[0o00002, 0o11111, 0o22222, 0o33333],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o033333));
+}
+
+#[test]
+fn index_modifies_order_code() {
+    // Adding 10000 to a CA turns it into a CS
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::index(0o100),
+            asm::ca(DATA + 1),
+            asm::tcf(0o4004),
+        ],
+        &[0o10000, 0o12345],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o165432));
+}
+
+#[test]
+fn subinstruction_sequence() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::index(0o100),
+            asm::cs(DATA),
+            asm::das(0o100),
+            asm::tcf(0o4005),
+        ],
+        &[0o00000],
+    );
+
+    let mut names = Vec::new();
+    for _ in 0..13 {
+        names.push(cpu.current_subsintruction_name());
+        cpu.step_subinstruction();
+    }
+    assert_eq!(
+        names,
+        [
+            "GOJ1", "TC0", "CA0", "STD2", "TS0", "STD2", "NDX0", "NDX1", "CS0", "STD2", "DAS0",
+            "DAS1", "STD2"
+        ]
+    );
+}
+
+fn multiply(lhs: u16, rhs: u16) -> (W16, W16) {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::mp(DATA + 1),
+            asm::tcf(0o4003),
+        ],
+        &[lhs, rhs],
+    );
+    run(&mut cpu, 20);
+    (cpu.a, cpu.l)
+}
+
+#[test]
+fn mp() {
+    let cases = [
+        // 3 * 5 = 15
+        (0o00003, 0o00005, 0o000000, 0o000017),
+        // 3 * -5 = -15
+        (0o00003, 0o77772, 0o177777, 0o177760),
+        // -3 * 5 = -15
+        (0o77774, 0o00005, 0o177777, 0o177760),
+        // -3 * -5 = 15
+        (0o77774, 0o77772, 0o000000, 0o000017),
+        // 0.5 * 0.5 = 0.25
+        (0o20000, 0o20000, 0o010000, 0o000000),
+        // Largest magnitudes
+        (0o37777, 0o37777, 0o037776, 0o000001),
+        (0o40000, 0o37777, 0o140001, 0o177776),
+        (0o12345, 0o23456, 0o006312, 0o022046),
+    ];
+    for (lhs, rhs, upper, lower) in cases.iter() {
+        assert_eq!(
+            multiply(*lhs, *rhs),
+            (W16::from(*upper), W16::from(*lower)),
+            "{:05o} * {:05o}",
+            lhs,
+            rhs
+        );
+    }
+}
+
+/// Reference model of MP on 15-bit one's complement operands
+fn reference_product(lhs: u16, rhs: u16) -> (W16, W16) {
+    let magnitude = |value: u16| {
+        if value & 0o40000 != 0 {
+            !value & 0o37777
+        } else {
+            value
+        }
+    };
+    let product = magnitude(lhs) as u32 * magnitude(rhs) as u32;
+    let upper = (product >> 14) as u16;
+    let lower = (product & 0o37777) as u16;
+
+    if (lhs ^ rhs) & 0o40000 != 0 {
+        (W16::from(!upper), W16::from(!lower))
+    } else {
+        (W16::from(upper), W16::from(lower))
+    }
+}
+
+#[test]
+fn mp_reference() {
+    let values = [
+        0o00000, 0o00001, 0o00002, 0o00777, 0o12345, 0o25252, 0o37776, 0o37777, 0o40000, 0o40001,
+        0o52525, 0o65432, 0o77000, 0o77776, 0o77777,
+    ];
+    for lhs in values.iter() {
+        for rhs in values.iter() {
+            assert_eq!(
+                multiply(*lhs, *rhs),
+                reference_product(*lhs, *rhs),
+                "{:05o} * {:05o}",
+                lhs,
+                rhs
+            );
+        }
+    }
+}
+
+#[test]
+fn mp_zero() {
+    // The sign of a zero product is the exclusive OR of the signs of the
+    // operands, in both words
+    let cases = [
+        (0o00000, 0o00000, 0o000000),
+        (0o00000, 0o77777, 0o177777),
+        (0o77777, 0o00000, 0o177777),
+        (0o77777, 0o77777, 0o000000),
+        (0o00000, 0o12345, 0o000000),
+        (0o00000, 0o65432, 0o177777),
+        (0o77777, 0o12345, 0o177777),
+        (0o77777, 0o65432, 0o000000),
+    ];
+    for (lhs, rhs, result) in cases.iter() {
+        assert_eq!(
+            multiply(*lhs, *rhs),
+            (W16::from(*result), W16::from(*result)),
+            "{:05o} * {:05o}",
+            lhs,
+            rhs
+        );
+    }
+}
+
+#[test]
+fn mp_square() {
+    // MP A squares the accumulator
+    let mut cpu = cpu_with_program(
+        &[asm::ca(DATA), asm::extend(), asm::mp(0o0), asm::tcf(0o4003)],
+        &[0o77770],
+    );
+    run(&mut cpu, 20);
+    assert_eq!((cpu.a, cpu.l), (W16::from(0o000000), W16::from(0o000061)));
+}
+
+fn divide(upper: u16, lower: u16, divisor: u16) -> (W16, W16) {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA + 1),
+            asm::ts(0o100),
+            asm::lxch(0o100),
+            asm::ca(DATA + 2),
+            asm::ts(0o101),
+            asm::ca(DATA),
+            asm::extend(),
+            asm::dv(0o101),
+            asm::tcf(0o4010),
+        ],
+        &[upper, lower, divisor],
+    );
+    run(&mut cpu, 30);
+    (cpu.a, cpu.l)
+}
+
+/// Reference model of DV on 15-bit one's complement operands, for
+/// dividends smaller in magnitude than the divisor
+fn reference_quotient(upper: u16, lower: u16, divisor: u16) -> (W16, W16) {
+    let value = |word: u16| {
+        if word & 0o40000 != 0 {
+            -((!word & 0o37777) as i32)
+        } else {
+            word as i32
+        }
+    };
+    let is_zero = |word: u16| word == 0o00000 || word == 0o77777;
+    let with_sign = |magnitude: u32, negative: bool| {
+        if negative {
+            W16::from(!(magnitude as u16))
+        } else {
+            W16::from(magnitude as u16)
+        }
+    };
+
+    let dividend = value(upper) * 0o40000 + value(lower);
+    let dividend_negative = if is_zero(upper) {
+        lower & 0o40000 != 0
+    } else {
+        upper & 0o40000 != 0
+    };
+    let divisor_negative = divisor & 0o40000 != 0;
+
+    let dividend = dividend.unsigned_abs();
+    let divisor = value(divisor).unsigned_abs();
+    (
+        with_sign(dividend / divisor, dividend_negative != divisor_negative),
+        with_sign(dividend % divisor, dividend_negative),
+    )
+}
+
+#[test]
+fn dv() {
+    let cases = [
+        // 0.25 / 0.5 = 0.5
+        (0o10000, 0o00000, 0o20000, 0o020000, 0o000000),
+        // 0.25 / -0.5 = -0.5
+        (0o10000, 0o00000, 0o57777, 0o157777, 0o000000),
+        // -0.25 / 0.5 = -0.5
+        (0o67777, 0o77777, 0o20000, 0o157777, 0o177777),
+        // -0.25 / -0.5 = 0.5
+        (0o67777, 0o77777, 0o57777, 0o020000, 0o177777),
+        // Remainder has the sign of the dividend
+        (0o00000, 0o00007, 0o00002, 0o000003, 0o000001),
+        (0o00000, 0o77770, 0o00002, 0o177774, 0o177776),
+        // A is zero: the sign of the dividend is the sign of L
+        (0o77777, 0o00007, 0o00002, 0o000003, 0o000001),
+        (0o00000, 0o77770, 0o77775, 0o000003, 0o177776),
+        // A and L have different signs
+        (0o00001, 0o77776, 0o20000, 0o000001, 0o017777),
+        (0o77776, 0o00001, 0o20000, 0o177776, 0o160000),
+        // Largest quotient
+        (0o37776, 0o37777, 0o37777, 0o037777, 0o037776),
+    ];
+    for (upper, lower, divisor, quotient, remainder) in cases.iter() {
+        assert_eq!(
+            divide(*upper, *lower, *divisor),
+            (W16::from(*quotient), W16::from(*remainder)),
+            "{:05o} {:05o} / {:05o}",
+            upper,
+            lower,
+            divisor
+        );
+    }
+}
+
+#[test]
+fn dv_reference() {
+    let words = [
+        0o00000, 0o00001, 0o00777, 0o12345, 0o25252, 0o37776, 0o37777, 0o40000, 0o40001, 0o52525,
+        0o65432, 0o77000, 0o77776, 0o77777,
+    ];
+    for upper in words.iter() {
+        for lower in words.iter() {
+            for divisor in words.iter() {
+                let magnitude = |word: u16| {
+                    if word & 0o40000 != 0 {
+                        !word & 0o37777
+                    } else {
+                        word
+                    }
+                };
+                // Only dividends smaller than the divisor give a defined
+                // result
+                if magnitude(*upper) >= magnitude(*divisor) {
+                    continue;
+                }
+                assert_eq!(
+                    divide(*upper, *lower, *divisor),
+                    reference_quotient(*upper, *lower, *divisor),
+                    "{:05o} {:05o} / {:05o}",
+                    upper,
+                    lower,
+                    divisor
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn dv_equal_magnitudes() {
+    // If the dividend and the divisor have the same magnitude, the quotient
+    // is the largest magnitude and the remainder is the divisor
+    assert_eq!(
+        divide(0o12345, 0o00000, 0o12345),
+        (W16::from(0o037777), W16::from(0o012345))
+    );
+    assert_eq!(
+        divide(0o12345, 0o00000, 0o65432),
+        (W16::from(0o140000), W16::from(0o012345))
+    );
+    assert_eq!(
+        divide(0o65432, 0o77777, 0o12345),
+        (W16::from(0o140000), W16::from(0o165432))
+    );
+}
+
+#[test]
+fn dv_undefined() {
+    // Results are undefined, but the divide sequence completes and the
+    // program continues
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ca(DATA + 1),
+            asm::extend(),
+            asm::dv(0o100),
+            asm::ca(DATA + 2),
+            asm::tcf(0o4006),
+        ],
+        &[0o00000, 0o12345, 0o00042],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o000042));
+}
+
+#[test]
+fn dv_stages() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::extend(),
+            asm::dv(0o100),
+            asm::ca(0o100),
+            asm::tcf(0o4005),
+        ],
+        &[0o20000],
+    );
+    run(&mut cpu, 7);
+
+    let mut names = Vec::new();
+    for _ in 0..7 {
+        names.push(cpu.current_subsintruction_name());
+        cpu.step_subinstruction();
+    }
+    assert_eq!(names, ["DV0", "DV1", "DV3", "DV7", "DV6", "DV4", "STD2"]);
+
+    // The divisor is left unchanged
+    run(&mut cpu, 4);
+    assert_eq!(cpu.a, W16::from(0o020000));
+}
+
+/// Run the extended instruction `op` with `lhs` in A and `rhs` in
+/// erasable 100. Returns A.
+fn subtract(op: fn(u16) -> u16, lhs: u16, rhs: u16) -> W16 {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA + 1),
+            asm::ts(0o100),
+            asm::ca(DATA),
+            asm::extend(),
+            op(0o100),
+            asm::tcf(0o4005),
+        ],
+        &[lhs, rhs],
+    );
+    run(&mut cpu, 20);
+    cpu.a
+}
+
+#[test]
+fn su() {
+    assert_eq!(subtract(asm::su, 0o00005, 0o00003), W16::from(0o000002));
+    assert_eq!(subtract(asm::su, 0o00003, 0o00005), W16::from(0o177775));
+    assert_eq!(subtract(asm::su, 0o77772, 0o77774), W16::from(0o177775));
+    assert_eq!(subtract(asm::su, 0o00000, 0o00000), W16::from(0o177777));
+    // Overflow
+    assert_eq!(subtract(asm::su, 0o37777, 0o77776), W16::from(0o040000));
+}
+
+/// Reference model of MSU: difference of two's complement counts,
+/// converted back to one's complement
+fn reference_difference(lhs: u16, rhs: u16) -> W16 {
+    let mut diff = lhs.wrapping_sub(rhs) & 0o77777;
+    if diff & 0o40000 != 0 {
+        diff -= 1;
+    }
+    if diff & 0o40000 != 0 {
+        diff |= 0o100000;
+    }
+    W16::from(diff)
+}
+
+#[test]
+fn msu() {
+    assert_eq!(subtract(asm::msu, 0o00005, 0o00003), W16::from(0o000002));
+    assert_eq!(subtract(asm::msu, 0o00003, 0o00005), W16::from(0o177775));
+    assert_eq!(subtract(asm::msu, 0o00000, 0o00000), W16::from(0o000000));
+    // Counts wrap around
+    assert_eq!(subtract(asm::msu, 0o00002, 0o77776), W16::from(0o000004));
+}
+
+#[test]
+fn msu_reference() {
+    let values = [
+        0o00000, 0o00001, 0o00002, 0o17777, 0o20000, 0o37776, 0o37777, 0o40000, 0o40001, 0o57777,
+        0o60000, 0o77775, 0o77776, 0o77777,
+    ];
+    for &lhs in values.iter() {
+        for &rhs in values.iter() {
+            assert_eq!(
+                subtract(asm::msu, lhs, rhs),
+                reference_difference(lhs, rhs),
+                "{:05o} - {:05o}",
+                lhs,
+                rhs
+            );
+        }
+    }
+}
+
+#[test]
+fn qxch() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ca(DATA + 1),
+            asm::ts(0o2),
+            asm::extend(),
+            asm::qxch(0o100),
+            asm::ca(0o100),
+            asm::tcf(0o4007),
+        ],
+        &[0o12345, 0o54321],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.q, W16::from(0o012345));
+    assert_eq!(cpu.a, W16::from(0o154321));
+}
+
+/// Run the extended instruction `op` on `value` stored in erasable 100.
+/// Returns the new content of 100.
+fn modify(op: fn(u16) -> u16, value: u16) -> W16 {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::extend(),
+            op(0o100),
+            asm::ca(0o100),
+            asm::tcf(0o4005),
+        ],
+        &[value],
+    );
+    run(&mut cpu, 20);
+    cpu.a
+}
+
+#[test]
+fn aug() {
+    assert_eq!(modify(asm::aug, 0o00005), W16::from(0o000006));
+    assert_eq!(modify(asm::aug, 0o77772), W16::from(0o177771));
+    assert_eq!(modify(asm::aug, 0o00000), W16::from(0o000001));
+    assert_eq!(modify(asm::aug, 0o77777), W16::from(0o177776));
+    // Overflow is lost when writing back to memory
+    assert_eq!(modify(asm::aug, 0o37777), W16::from(0o000000));
+}
+
+#[test]
+fn dim() {
+    assert_eq!(modify(asm::dim, 0o00005), W16::from(0o000004));
+    assert_eq!(modify(asm::dim, 0o00001), W16::from(0o000000));
+    assert_eq!(modify(asm::dim, 0o77772), W16::from(0o177773));
+    assert_eq!(modify(asm::dim, 0o77776), W16::from(0o177777));
+    assert_eq!(modify(asm::dim, 0o00000), W16::from(0o000000));
+    assert_eq!(modify(asm::dim, 0o77777), W16::from(0o177777));
+}
+
+fn double_load(op: fn(u16) -> u16, upper: u16, lower: u16) -> (W16, W16) {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ca(DATA + 1),
+            asm::ts(0o101),
+            asm::extend(),
+            op(0o100),
+            asm::tcf(0o4006),
+        ],
+        &[upper, lower],
+    );
+    run(&mut cpu, 20);
+    (cpu.a, cpu.l)
+}
+
+#[test]
+fn dca() {
+    assert_eq!(
+        double_load(asm::dca, 0o12345, 0o54321),
+        (W16::from(0o012345), W16::from(0o154321))
+    );
+    assert_eq!(
+        double_load(asm::dca, 0o00000, 0o77777),
+        (W16::from(0o000000), W16::from(0o177777))
+    );
+}
+
+#[test]
+fn dcs() {
+    assert_eq!(
+        double_load(asm::dcs, 0o12345, 0o54321),
+        (W16::from(0o165432), W16::from(0o023456))
+    );
+    assert_eq!(
+        double_load(asm::dcs, 0o00000, 0o77777),
+        (W16::from(0o177777), W16::from(0o000000))
+    );
+}
+
+#[test]
+fn dca_registers() {
+    // K+1 is loaded first, so DCA L loads Q into both A and L
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o2),
+            asm::ca(DATA + 1),
+            asm::ts(0o1),
+            asm::extend(),
+            asm::dca(0o1),
+            asm::tcf(0o4006),
+        ],
+        &[0o00011, 0o00022],
+    );
+    run(&mut cpu, 20);
+    assert_eq!((cpu.a, cpu.l), (W16::from(0o000011), W16::from(0o000011)));
+}
+
+/// Run BZF or BZMF with `value` in A. Returns whether the branch was taken.
+fn branch(op: fn(u16) -> u16, value: u16) -> bool {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            op(0o4005),
+            asm::ca(DATA + 1),
+            asm::tcf(0o4004),
+            asm::ca(DATA + 2),
+            asm::tcf(0o4006),
+        ],
+        &[value, 0o00001, 0o00002],
+    );
+    run(&mut cpu, 20);
+    cpu.a == W16::from(0o000002)
+}
+
+#[test]
+fn bzf() {
+    assert!(branch(asm::bzf, 0o00000));
+    assert!(branch(asm::bzf, 0o77777));
+    assert!(!branch(asm::bzf, 0o00001));
+    assert!(!branch(asm::bzf, 0o77776));
+}
+
+#[test]
+fn bzmf() {
+    assert!(branch(asm::bzmf, 0o00000));
+    assert!(branch(asm::bzmf, 0o77777));
+    assert!(branch(asm::bzmf, 0o77772));
+    assert!(!branch(asm::bzmf, 0o00005));
+}
+
+#[test]
+fn extended_stages() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::bzf(0o4003),
+            asm::extend(),
+            asm::dca(0o100),
+            asm::tcf(0o4005),
+        ],
+        &[0o00000],
+    );
+    run(&mut cpu, 2);
+
+    let mut names = Vec::new();
+    for _ in 0..7 {
+        names.push(cpu.current_subsintruction_name());
+        cpu.step_subinstruction();
+    }
+    assert_eq!(
+        names,
+        ["CA0", "STD2", "STD2", "BZF0", "STD2", "DCA0", "DCA1"]
+    );
+}
+
+/// Run the channel instruction `op` on channel 10 with `value` in A and
+/// `channel` in the channel. Returns A and the channel.
+fn channel_op(op: fn(u16) -> u16, value: u16, channel: u16) -> (W16, W15) {
+    let mut cpu = cpu_with_program(
+        &[asm::ca(DATA), asm::extend(), op(0o10), asm::tcf(0o4003)],
+        &[value],
+    );
+    cpu.write_channel(W9::from(0o10), W15::from(channel));
+    run(&mut cpu, 20);
+    (cpu.a, cpu.read_channel(W9::from(0o10)))
+}
+
+#[test]
+fn read_write() {
+    assert_eq!(
+        channel_op(asm::read, 0o12345, 0o54321),
+        (W16::from(0o154321), W15::from(0o54321))
+    );
+    assert_eq!(
+        channel_op(asm::write, 0o12345, 0o54321),
+        (W16::from(0o012345), W15::from(0o12345))
+    );
+}
+
+#[test]
+fn channel_logic() {
+    let a = 0o52525;
+    let c = 0o33333;
+    // Results read back into A are sign-extended
+    let expected =
+        |value: u16| MemoryWord::with_proper_parity(W15::from(value)).as_register_value();
+
+    assert_eq!(channel_op(asm::rand, a, c), (expected(a & c), W15::from(c)));
+    assert_eq!(
+        channel_op(asm::wand, a, c),
+        (expected(a & c), W15::from(a & c))
+    );
+    assert_eq!(channel_op(asm::ror, a, c), (expected(a | c), W15::from(c)));
+    assert_eq!(
+        channel_op(asm::wor, a, c),
+        (expected(a | c), W15::from(a | c))
+    );
+    assert_eq!(channel_op(asm::rxor, a, c), (expected(a ^ c), W15::from(c)));
+}
+
+#[test]
+fn rxor_editing_channel() {
+    // Channel 20 is also the CYR editing address, which must not edit the
+    // intermediate result, and the channel is only read once
+    let a = 0o52525;
+    let c = 0o33333;
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::rxor(0o20),
+            asm::tcf(0o4003),
+        ],
+        &[a],
+    );
+    cpu.write_channel(W9::from(0o20), W15::from(c));
+    while cpu.current_subsintruction_name() != "RXOR0" {
+        cpu.step_subinstruction();
+    }
+
+    let mut reads = 0;
+    for _ in 0..12 {
+        cpu.step_control_pulse();
+        reads += cpu
+            .accesses()
+            .iter()
+            .filter(|access| **access == MemoryAccess::ChannelRead(W9::from(0o20)))
+            .count();
+    }
+    assert_eq!(reads, 1);
+    assert_eq!(
+        cpu.a,
+        MemoryWord::with_proper_parity(W15::from(a ^ c)).as_register_value()
+    );
+}
+
+#[test]
+fn channel_keeps_memory() {
+    // The channel address is not an erasable memory access
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o30),
+            asm::ca(DATA + 1),
+            asm::extend(),
+            asm::write(0o30),
+            asm::extend(),
+            asm::read(0o31),
+            asm::ca(0o30),
+            asm::tcf(0o4010),
+        ],
+        &[0o00123, 0o00456],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o000123));
+    assert_eq!(cpu.read_channel(W9::from(0o30)), W15::from(0o00456));
+}
+
+#[test]
+fn erasable_writes() {
+    // TS writes the same value twice, CA and MASK only restore the word read
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o100),
+            asm::ts(0o100),
+            asm::ca(0o100),
+            asm::mask(0o100),
+            asm::tcf(0o4005),
+        ],
+        &[0o00123],
+    );
+    let mut writes = Vec::new();
+    for _ in 0..10 * 12 {
+        cpu.step_control_pulse();
+        let write = MemoryAccess::ErasableWrite(W3::zero(), W8::from(0o100));
+        if cpu.accesses().contains(&write) {
+            writes.push(cpu.current_subsintruction_name());
+        }
+    }
+    assert_eq!(writes, ["TS0", "TS0"]);
+}
+
+#[test]
+fn channels_l_q() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::write(0o2),
+            asm::ca(DATA + 1),
+            asm::extend(),
+            asm::write(0o1),
+            asm::extend(),
+            asm::read(0o2),
+            asm::tcf(0o4010),
+        ],
+        &[0o12345, 0o70000],
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.q, W16::from(0o012345));
+    assert_eq!(cpu.l, W16::from(0o170000));
+    assert_eq!(cpu.a, W16::from(0o012345));
+
+    // The public API sees the same registers
+    assert_eq!(cpu.read_channel(W9::from(0o1)), W15::from(0o70000));
+    cpu.write_channel(W9::from(0o2), W15::from(0o40001));
+    assert_eq!(cpu.q, W16::from(0o140001));
+}
+
+/// Peripheral answering reads with a constant and recording writes
+#[derive(Default)]
+struct Recorder {
+    writes: Vec<(W9, W15)>,
+    ticks: u16,
+}
+
+impl Peripheral for Recorder {
+    fn read(&mut self, channel: W9, stored: W15) -> W15 {
+        if channel == W9::from(0o11) {
+            W15::from(0o00321)
+        } else {
+            stored
+        }
+    }
+
+    fn write(&mut self, channel: W9, value: W15) {
+        self.writes.push((channel, value));
+    }
+
+    fn tick(&mut self, channels: &mut Channels, _requests: &mut Requests) {
+        self.ticks += 1;
+        channels.write(W9::from(0o12), W15::from(self.ticks));
+    }
+}
+
+#[test]
+fn peripheral() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::write(0o10),
+            asm::extend(),
+            asm::write(0o13),
+            asm::extend(),
+            asm::read(0o11),
+            asm::tcf(0o4007),
+        ],
+        &[0o00077],
+    );
+    let recorder = Rc::new(RefCell::new(Recorder::default()));
+    cpu.attach_peripheral(W9::from(0o10)..=W9::from(0o12), Box::new(recorder.clone()));
+    run(&mut cpu, 20);
+
+    // Reads are answered by the peripheral, writes outside its range are not reported
+    assert_eq!(cpu.a, W16::from(0o000321));
+    assert_eq!(
+        recorder.borrow().writes,
+        [(W9::from(0o10), W15::from(0o00077))]
+    );
+    assert_eq!(cpu.read_channel(W9::from(0o10)), W15::from(0o00077));
+    assert_eq!(cpu.read_channel(W9::from(0o13)), W15::from(0o00077));
+
+    // Ticked once per MCT
+    assert_eq!(recorder.borrow().ticks, 20);
+    assert_eq!(cpu.read_channel(W9::from(0o12)), W15::from(20));
+}
+
+/// Create a CPU with words at given addresses of the fixed-fixed bank 2
+/// (octal 4000 to 5777)
+fn cpu_with_words(words: &[(u16, u16)], data: &[u16]) -> Cpu {
+    let size = words.iter().map(|(address, _)| address - 0o4000 + 1).max();
+    let mut program = vec![0; size.unwrap_or(0) as usize];
+    for (address, word) in words {
+        program[(address - 0o4000) as usize] = *word;
+    }
+    cpu_with_program(&program, data)
+}
+
+/// Service routines for T6RUPT, T5RUPT, T3RUPT, T4RUPT and KEYRUPT1, with
+/// a jump to the main loop at 4060
+fn interrupt_routines() -> Vec<(u16, u16)> {
+    vec![
+        (0o4000, asm::tcf(0o4060)),
+        // T6RUPT
+        (0o4004, asm::ca(DATA)),
+        (0o4005, asm::tcf(0o4005)),
+        // T5RUPT, counting in 101
+        (0o4010, asm::incr(0o101)),
+        (0o4011, asm::resume()),
+        // T3RUPT, with BRUPT in L and ZRUPT in A
+        (0o4014, asm::ca(BRUPT)),
+        (0o4015, asm::ts(0o1)),
+        (0o4016, asm::ca(ZRUPT)),
+        (0o4017, asm::tcf(0o4017)),
+        // T4RUPT, counting in 104 and restoring A, L, Q and BB
+        (0o4020, asm::tcf(0o4040)),
+        // KEYRUPT1
+        (0o4024, asm::ca(DATA + 1)),
+        (0o4025, asm::tcf(0o4025)),
+        (0o4040, asm::ts(ARUPT)),
+        (0o4041, asm::lxch(LRUPT)),
+        (0o4042, asm::extend()),
+        (0o4043, asm::qxch(QRUPT)),
+        (0o4044, asm::ca(0o6)),
+        (0o4045, asm::ts(BBRUPT)),
+        (0o4046, asm::incr(0o104)),
+        (0o4047, asm::cs(0o104)),
+        (0o4050, asm::ts(0o2)),
+        (0o4051, asm::ca(BBRUPT)),
+        (0o4052, asm::ts(0o6)),
+        (0o4053, asm::extend()),
+        (0o4054, asm::qxch(QRUPT)),
+        (0o4055, asm::lxch(LRUPT)),
+        (0o4056, asm::ca(ARUPT)),
+        (0o4057, asm::resume()),
+    ]
+}
+
+/// Program with the service routines and a main loop starting at 4060
+fn cpu_with_interrupts(main: &[u16], data: &[u16]) -> Cpu {
+    let mut words = interrupt_routines();
+    for (offset, word) in main.iter().enumerate() {
+        words.push((0o4060 + offset as u16, *word));
+    }
+    cpu_with_words(&words, data)
+}
+
+#[test]
+fn interrupt() {
+    let mut cpu = cpu_with_interrupts(&[asm::tcf(0o4060)], &[]);
+    run(&mut cpu, 10);
+    assert!(!cpu.is_interrupt_in_progress());
+
+    cpu.request_interrupt(Interrupt::T3);
+    assert!(cpu.is_interrupt_pending(Interrupt::T3));
+
+    let mut names = Vec::new();
+    for _ in 0..4 {
+        names.push(cpu.current_subsintruction_name());
+        cpu.step_subinstruction();
+    }
+    assert_eq!(names, ["TCF0", "RUPT0", "RUPT1", "RUPT3"]);
+    assert!(!cpu.is_interrupt_pending(Interrupt::T3));
+    assert!(cpu.is_interrupt_in_progress());
+
+    // Z points after the interrupted TCF, which is saved in BRUPT
+    run(&mut cpu, 10);
+    assert_eq!(cpu.a, W16::from(0o004061));
+    assert_eq!(cpu.l, W16::from(asm::tcf(0o4060)));
+}
+
+#[test]
+fn interrupt_priority() {
+    let mut cpu = cpu_with_interrupts(&[asm::tcf(0o4060)], &[0o00111, 0o00222]);
+    run(&mut cpu, 10);
+
+    cpu.request_interrupt(Interrupt::Key1);
+    cpu.request_interrupt(Interrupt::T6);
+    run(&mut cpu, 20);
+
+    // No other interrupt is taken during the service routine
+    assert_eq!(cpu.a, W16::from(0o000111));
+    assert!(!cpu.is_interrupt_pending(Interrupt::T6));
+    assert!(cpu.is_interrupt_pending(Interrupt::Key1));
+}
+
+#[test]
+fn interrupt_inhibited() {
+    let mut cpu = cpu_with_interrupts(&[asm::inhint(), asm::tcf(0o4061)], &[]);
+    run(&mut cpu, 10);
+
+    cpu.request_interrupt(Interrupt::T6);
+    run(&mut cpu, 20);
+    assert!(cpu.is_interrupt_pending(Interrupt::T6));
+    assert!(!cpu.is_interrupt_in_progress());
+}
+
+#[test]
+fn interrupt_overflow() {
+    let mut cpu = cpu_with_interrupts(
+        &[asm::ca(DATA), asm::ad(DATA + 1), asm::tcf(0o4062)],
+        &[0o37777, 0o00001],
+    );
+    run(&mut cpu, 10);
+
+    cpu.request_interrupt(Interrupt::T6);
+    run(&mut cpu, 20);
+    assert!(cpu.is_interrupt_pending(Interrupt::T6));
+    assert_eq!(cpu.a, W16::from(0o040000));
+}
+
+#[test]
+fn interrupt_extend() {
+    // The interrupt is never taken between EXTEND and the extended instruction
+    let mut cpu = cpu_with_interrupts(&[asm::extend(), asm::qxch(0o100), asm::tcf(0o4060)], &[]);
+    run(&mut cpu, 10);
+
+    // Request the interrupt in the STD2 fetching QXCH
+    while cpu.current_subsintruction_name() != "TCF0" {
+        cpu.step_subinstruction();
+    }
+    cpu.step_subinstruction();
+    cpu.request_interrupt(Interrupt::T3);
+    run(&mut cpu, 20);
+    assert!(cpu.is_interrupt_in_progress());
+    assert_eq!(cpu.l, W16::from(asm::tcf(0o4060)));
+}
+
+/// Run `instruction` `times` times on a counter cell holding `initial`.
+/// Returns the new content of the counter.
+fn count(instruction: CounterInstruction, initial: u16, times: usize) -> W16 {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(counters::CDUX.address()),
+            asm::ca(counters::CDUX.address()),
+            asm::tcf(0o4002),
+        ],
+        &[initial],
+    );
+    run(&mut cpu, 6);
+    for _ in 0..times {
+        // Wait for each request to be serviced, as identical pending
+        // requests are merged
+        cpu.request_counter(counters::CDUX, instruction);
+        run(&mut cpu, 2);
+    }
+    run(&mut cpu, 10);
+    cpu.a
+}
+
+#[test]
+fn counter_requests_merged() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(counters::CDUX.address()),
+            asm::ca(counters::CDUX.address()),
+            asm::tcf(0o4002),
+        ],
+        &[0o00005],
+    );
+    run(&mut cpu, 6);
+    for _ in 0..3 {
+        cpu.request_counter(counters::CDUX, CounterInstruction::Pinc);
+    }
+    cpu.request_counter(counters::CDUX, CounterInstruction::Shinc);
+    run(&mut cpu, 10);
+    assert_eq!(cpu.a, W16::from(0o000014));
+}
+
+#[test]
+fn pinc_minc() {
+    assert_eq!(
+        count(CounterInstruction::Pinc, 0o00005, 3),
+        W16::from(0o000010)
+    );
+    assert_eq!(
+        count(CounterInstruction::Pinc, 0o77777, 1),
+        W16::from(0o000001)
+    );
+    assert_eq!(
+        count(CounterInstruction::Pinc, 0o37777, 1),
+        W16::from(0o000000)
+    );
+    assert_eq!(
+        count(CounterInstruction::Minc, 0o00005, 1),
+        W16::from(0o000004)
+    );
+    assert_eq!(
+        count(CounterInstruction::Minc, 0o00000, 1),
+        W16::from(0o177776)
+    );
+    assert_eq!(
+        count(CounterInstruction::Minc, 0o00001, 1),
+        W16::from(0o177777)
+    );
+}
+
+#[test]
+fn dinc() {
+    assert_eq!(
+        count(CounterInstruction::Dinc, 0o00005, 1),
+        W16::from(0o000004)
+    );
+    assert_eq!(
+        count(CounterInstruction::Dinc, 0o00001, 1),
+        W16::from(0o177777)
+    );
+    assert_eq!(
+        count(CounterInstruction::Dinc, 0o77772, 1),
+        W16::from(0o177773)
+    );
+    assert_eq!(
+        count(CounterInstruction::Dinc, 0o77776, 1),
+        W16::from(0o177777)
+    );
+    assert_eq!(
+        count(CounterInstruction::Dinc, 0o77777, 2),
+        W16::from(0o177777)
+    );
+    assert_eq!(
+        count(CounterInstruction::Dinc, 0o00000, 1),
+        W16::from(0o000000)
+    );
+}
+
+#[test]
+fn pcdu_mcdu() {
+    assert_eq!(
+        count(CounterInstruction::Pcdu, 0o00005, 2),
+        W16::from(0o000007)
+    );
+    assert_eq!(
+        count(CounterInstruction::Pcdu, 0o77777, 1),
+        W16::from(0o000000)
+    );
+    assert_eq!(
+        count(CounterInstruction::Pcdu, 0o37777, 1),
+        W16::from(0o140000)
+    );
+    assert_eq!(
+        count(CounterInstruction::Mcdu, 0o00000, 1),
+        W16::from(0o177777)
+    );
+    assert_eq!(
+        count(CounterInstruction::Mcdu, 0o40000, 1),
+        W16::from(0o037777)
+    );
+}
+
+#[test]
+fn shinc_shanc() {
+    assert_eq!(
+        count(CounterInstruction::Shinc, 0o12345, 1),
+        W16::from(0o024712)
+    );
+    assert_eq!(
+        count(CounterInstruction::Shinc, 0o60001, 1),
+        W16::from(0o140002)
+    );
+    assert_eq!(
+        count(CounterInstruction::Shanc, 0o12345, 1),
+        W16::from(0o024713)
+    );
+    assert_eq!(
+        count(CounterInstruction::Shanc, 0o00000, 3),
+        W16::from(0o000007)
+    );
+}
+
+#[test]
+fn counter_keeps_sequence() {
+    // Counter subinstructions between the MCTs do not disturb MP
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::mp(DATA + 1),
+            asm::tcf(0o4003),
+        ],
+        &[0o12345, 0o23456],
+    );
+    let mut names = Vec::new();
+    for step in 0..40 {
+        if step % 2 == 0 {
+            cpu.request_counter(counters::TIME1, CounterInstruction::Pinc);
+        }
+        names.push(cpu.current_subsintruction_name());
+        cpu.step_subinstruction();
+    }
+    assert!(names.contains(&"PINC"));
+    assert_eq!((cpu.a, cpu.l), multiply(0o12345, 0o23456));
+}
+
+#[test]
+fn counter_overflow_interrupt() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::inhint(),
+            asm::ca(DATA),
+            asm::ts(counters::TIME3.address()),
+            asm::tcf(0o4003),
+        ],
+        &[0o37777],
+    );
+    run(&mut cpu, 10);
+    assert!(!cpu.is_interrupt_pending(Interrupt::T3));
+
+    cpu.request_counter(counters::TIME3, CounterInstruction::Pinc);
+    run(&mut cpu, 2);
+    assert!(cpu.is_interrupt_pending(Interrupt::T3));
+}
+
+/// Peripheral sending a few PIPA pulses
+struct Accelerometer {
+    ticks: usize,
+}
+
+impl Peripheral for Accelerometer {
+    fn tick(&mut self, _channels: &mut Channels, requests: &mut Requests) {
+        self.ticks += 1;
+        if self.ticks.is_multiple_of(3) && self.ticks <= 15 {
+            requests.counter(counters::PIPAX, CounterInstruction::Minc);
+        }
+    }
+}
+
+#[test]
+fn peripheral_counter() {
+    let mut cpu = cpu_with_program(&[asm::ca(counters::PIPAX.address()), asm::tcf(0o4000)], &[]);
+    cpu.attach_peripheral(
+        W9::from(0o0)..=W9::from(0o0),
+        Box::new(Accelerometer { ticks: 0 }),
+    );
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o177772));
+}
+
+/// Run a loop reading `counter` into A, after writing `initial` to it
+fn cpu_reading_counter(counter: CounterCell, initial: u16) -> Cpu {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::inhint(),
+            asm::ca(DATA),
+            asm::ts(counter.address()),
+            asm::ca(counter.address()),
+            asm::tcf(0o4003),
+        ],
+        &[initial],
+    );
+    run(&mut cpu, 8);
+    cpu
+}
+
+#[test]
+fn timers() {
+    let mut cpu = cpu_reading_counter(counters::TIME1, 0o00000);
+
+    // TIME1 counts centiseconds
+    while cpu.timepulses() < 25000 {
+        cpu.step_subinstruction();
+    }
+    run(&mut cpu, 2);
+    assert_eq!(cpu.a, W16::from(0o000002));
+}
+
+#[test]
+fn time1_overflow() {
+    let mut cpu = cpu_reading_counter(counters::TIME1, 0o37777);
+    while cpu.timepulses() < 10240 {
+        cpu.step_subinstruction();
+    }
+    run(&mut cpu, 8);
+    assert_eq!(cpu.a, W16::from(0o000000));
+
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(counters::TIME1.address()),
+            asm::ca(counters::TIME2.address()),
+            asm::tcf(0o4002),
+        ],
+        &[0o37777],
+    );
+    while cpu.timepulses() < 10240 {
+        cpu.step_subinstruction();
+    }
+    run(&mut cpu, 8);
+    assert_eq!(cpu.a, W16::from(0o000001));
+}
+
+#[test]
+fn timer_interrupts() {
+    for (counter, interrupt, delay) in [
+        (counters::TIME3, Interrupt::T3, 10240),
+        (counters::TIME4, Interrupt::T4, 5120),
+        (counters::TIME5, Interrupt::T5, 10240),
+    ]
+    .iter()
+    {
+        let mut cpu = cpu_reading_counter(*counter, 0o37777);
+        while cpu.timepulses() < delay - 12 {
+            cpu.step_subinstruction();
+        }
+        assert!(!cpu.is_interrupt_pending(*interrupt));
+
+        run(&mut cpu, 8);
+        assert!(cpu.is_interrupt_pending(*interrupt));
+        assert_eq!(cpu.a, W16::from(0o000000));
+    }
+}
+
+#[test]
+fn time6() {
+    let mut cpu = cpu_reading_counter(counters::TIME6, 0o00002);
+
+    // Disabled
+    while cpu.timepulses() < 2000 {
+        cpu.step_subinstruction();
+    }
+    assert_eq!(cpu.a, W16::from(0o000002));
+
+    // Enabled, counts down to -0 then requests T6RUPT
+    cpu.write_channel(W9::from(0o13), W15::from(0o40000));
+    while cpu.timepulses() < 2000 + 3 * 640 + 100 {
+        cpu.step_subinstruction();
+    }
+    assert_eq!(cpu.a, W16::from(0o177777));
+    assert!(cpu.is_interrupt_pending(Interrupt::T6));
+    assert_eq!(cpu.read_channel(W9::from(0o13)), W15::zero());
+}
+
+/// Write `value` to the editing register at `address`, then read it back
+/// twice. Returns A.
+fn edit(address: u16, value: u16) -> W16 {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ts(address),
+            asm::ca(address),
+            asm::ca(address),
+            asm::tcf(0o4004),
+        ],
+        &[value],
+    );
+    run(&mut cpu, 20);
+    cpu.a
+}
+
+#[test]
+fn cyr() {
+    assert_eq!(edit(0o20, 0o00001), W16::from(0o140000));
+    assert_eq!(edit(0o20, 0o12345), W16::from(0o145162));
+    assert_eq!(edit(0o20, 0o40000), W16::from(0o020000));
+}
+
+#[test]
+fn sr() {
+    assert_eq!(edit(0o21, 0o00006), W16::from(0o000003));
+    assert_eq!(edit(0o21, 0o40001), W16::from(0o160000));
+    assert_eq!(edit(0o21, 0o77777), W16::from(0o177777));
+}
+
+#[test]
+fn cyl() {
+    assert_eq!(edit(0o22, 0o40001), W16::from(0o000003));
+    assert_eq!(edit(0o22, 0o20000), W16::from(0o140000));
+    assert_eq!(edit(0o22, 0o12345), W16::from(0o024712));
+}
+
+#[test]
+fn edop() {
+    assert_eq!(edit(0o23, 0o12345), W16::from(0o000051));
+    assert_eq!(edit(0o23, 0o77777), W16::from(0o000177));
+}
+
+#[test]
+fn edit_overflow() {
+    // With an overflow in A, bit 15 of A is shifted in instead of the sign.
+    // TS skips the next instruction.
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::ad(DATA),
+            asm::ts(0o20),
+            asm::tcf(0o4004),
+            asm::ca(0o20),
+            asm::tcf(0o4004),
+        ],
+        &[0o20000],
+    );
+    run(&mut cpu, 20);
+    assert_eq!(cpu.a, W16::from(0o020000));
+}
+
+/// Read switched fixed address 2000 with the given FBANK and superbank bit
+fn read_fixed_bank(fbank: u16, superbank: bool) -> W16 {
+    let mut storage = FixedStorage::new();
+    storage.write(
+        W6::from(2),
+        W10::zero(),
+        MemoryWord::with_proper_parity(W15::from(asm::ca(0o2000))),
+    );
+    for bank in &[0o30, 0o33, 0o34, 0o40, 0o43] {
+        storage.write(
+            W6::from(*bank),
+            W10::zero(),
+            MemoryWord::with_proper_parity(W15::from(*bank)),
+        );
+    }
+
+    let mut cpu = Cpu::new(storage);
+    cpu.fbank = W5::from(fbank);
+    if superbank {
+        cpu.write_channel(W9::from(0o7), W15::from(0o100));
+    }
+    run(&mut cpu, 3);
+    cpu.a
+}
+
+#[test]
+fn superbank() {
+    assert_eq!(read_fixed_bank(0o30, false), W16::from(0o30));
+    assert_eq!(read_fixed_bank(0o30, true), W16::from(0o40));
+    assert_eq!(read_fixed_bank(0o33, true), W16::from(0o43));
+    assert_eq!(read_fixed_bank(0o34, false), W16::from(0o34));
+    // Superbank bit only affects banks 30 to 37
+    assert_eq!(read_fixed_bank(0o03, true), W16::zero());
+    // Banks 44 to 47 do not exist
+    assert_eq!(read_fixed_bank(0o34, true), W16::zero());
+}
+
+/// Run until a hardware alarm restarts the computer, at most 20 MCTs
+fn run_until_alarm(cpu: &mut Cpu) -> Option<Alarm> {
+    run_until_alarm_timepulses(cpu, 20 * 12)
+}
+
+/// Run until a hardware alarm restarts the computer, at most the given
+/// number of timepulses since power-on
+fn run_until_alarm_timepulses(cpu: &mut Cpu, timepulses: u64) -> Option<Alarm> {
+    while cpu.alarm().is_none() && cpu.timepulses() < timepulses {
+        cpu.step_subinstruction();
+    }
+    cpu.alarm()
+}
+
+fn cpu_with_bad_fixed_word() -> Cpu {
+    let mut storage = FixedStorage::new();
+    for (offset, word) in [asm::ca(DATA), asm::tcf(0o4001)].iter().enumerate() {
+        storage.write(
+            W6::from(2),
+            W10::from(offset as u16),
+            MemoryWord::with_proper_parity(W15::from(*word)),
+        );
+    }
+    storage.write(
+        W6::from(2),
+        W10::from(DATA - 0o4000),
+        MemoryWord::with_wrong_parity(W15::from(0o12345)),
+    );
+    Cpu::new(storage)
+}
+
+#[test]
+fn fixed_parity_fail() {
+    let mut cpu = cpu_with_bad_fixed_word();
+    assert_eq!(run_until_alarm(&mut cpu), Some(Alarm::FixedParity));
+    assert_eq!(cpu.read_channel(W9::from(0o77)), W15::from(0o1));
+    // GOJAM restarts at 4000 through GOJ1
+    assert_eq!(cpu.current_subsintruction_name(), "GOJ1");
+    run(&mut cpu, 1);
+    assert_eq!(cpu.s.inner(), W12::from(0o4000));
+}
+
+#[test]
+fn parity_check_disabled() {
+    let mut cpu = cpu_with_bad_fixed_word();
+    cpu.set_parity_check(false);
+    assert_eq!(run_until_alarm(&mut cpu), None);
+    assert_eq!(cpu.a, W16::from(0o012345));
+    assert_eq!(cpu.read_channel(W9::from(0o77)), W15::zero());
+}
+
+#[test]
+fn erasable_parity_fail() {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(0o100),
+            asm::extend(),
+            asm::write(0o77),
+            asm::tcf(0o4003),
+        ],
+        &[],
+    );
+    let bad = MemoryWord::with_wrong_parity(W15::from(0o123));
+    cpu.write_erasable(W3::zero(), W8::from(0o100), bad);
+    assert_eq!(run_until_alarm(&mut cpu), Some(Alarm::ErasableParity));
+    assert_eq!(cpu.read_channel(W9::from(0o77)), W15::from(0o3));
+    // The failed word is not written back
+    assert_eq!(cpu.read_erasable(W3::zero(), W8::from(0o100)), bad);
+
+    // The alarm is latched until the program writes channel 77
+    let good = MemoryWord::with_proper_parity(W15::from(0o123));
+    cpu.write_erasable(W3::zero(), W8::from(0o100), good);
+    run(&mut cpu, 2);
+    assert_eq!(cpu.alarm(), Some(Alarm::ErasableParity));
+    run(&mut cpu, 10);
+    assert_eq!(cpu.alarm(), None);
+    assert_eq!(cpu.read_channel(W9::from(0o77)), W15::zero());
+    assert_eq!(cpu.a, W16::from(0o000123));
+}
+
+#[test]
+fn tc_trap() {
+    let mut cpu = cpu_with_program(&[asm::tcf(0o4000)], &[]);
+    cpu.set_restart_alarms(true);
+    assert_eq!(
+        run_until_alarm_timepulses(&mut cpu, 20480),
+        Some(Alarm::TcTrap)
+    );
+    assert_eq!(cpu.read_channel(W9::from(0o77)), W15::from(0o4));
+    assert_eq!(cpu.current_subsintruction_name(), "GOJ1");
+
+    // Disabled by default
+    let mut cpu = cpu_with_program(&[asm::tcf(0o4000)], &[]);
+    assert_eq!(run_until_alarm_timepulses(&mut cpu, 20480), None);
+}
+
+#[test]
+fn rupt_lock() {
+    // No interrupt ever occurs
+    let mut cpu = cpu_with_program(&[asm::ca(0o67), asm::tcf(0o4000)], &[]);
+    cpu.set_restart_alarms(true);
+    assert_eq!(run_until_alarm_timepulses(&mut cpu, 16 * 10240 - 12), None);
+    assert_eq!(
+        run_until_alarm_timepulses(&mut cpu, 16 * 10240 + 12),
+        Some(Alarm::RuptLock)
+    );
+    assert_eq!(cpu.read_channel(W9::from(0o77)), W15::from(0o10));
+}
+
+/// Program which loads DATA into A, then writes channels 7 and 11 and
+/// erasable memory
+fn cpu_before_reset() -> Cpu {
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::write(0o7),
+            asm::extend(),
+            asm::write(0o11),
+            asm::ts(0o100),
+            asm::tcf(0o4006),
+        ],
+        &[0o100],
+    );
+    cpu.write_channel(W9::from(0o30), W15::from(0o12345));
+    run(&mut cpu, 12);
+    assert_eq!(cpu.read_channel(W9::from(0o7)), W15::from(0o100));
+    assert_eq!(cpu.read_channel(W9::from(0o11)), W15::from(0o100));
+    cpu
+}
+
+#[test]
+fn reset() {
+    let mut cpu = cpu_before_reset();
+    cpu.request_interrupt(Interrupt::T3);
+    cpu.reset();
+
+    assert_eq!(cpu.current_subsintruction_name(), "GOJ1");
+    assert!(!cpu.is_interrupt_pending(Interrupt::T3));
+    assert_eq!(cpu.a, W16::from(0o100));
+    assert_eq!(cpu.read_channel(W9::from(0o7)), W15::zero());
+    assert_eq!(cpu.read_channel(W9::from(0o11)), W15::zero());
+    assert_eq!(cpu.read_channel(W9::from(0o30)), W15::from(0o12345));
+    assert_eq!(cpu.read_channel(W9::from(0o77)), W15::zero());
+    assert_eq!(cpu.alarm(), None);
+    assert_eq!(
+        cpu.read_erasable(W3::zero(), W8::from(0o100)).value(),
+        W15::from(0o100)
+    );
+
+    // The program restarts the same way as after power-on
+    let mut fresh = cpu_before_reset();
+    fresh.power_on();
+    assert_eq!(
+        subinstruction_names(&mut cpu),
+        subinstruction_names(&mut fresh)
+    );
+    assert_eq!(cpu.z, W16::from(0o4002));
+}
+
+fn subinstruction_names(cpu: &mut Cpu) -> Vec<&'static str> {
+    (0..4)
+        .map(|_| {
+            let name = cpu.current_subsintruction_name();
+            cpu.step_subinstruction();
+            name
+        })
+        .collect()
+}
+
+#[test]
+fn power_on() {
+    let mut cpu = cpu_before_reset();
+    cpu.power_on();
+
+    assert_eq!(cpu.current_subsintruction_name(), "GOJ1");
+    assert_eq!(cpu.a, W16::zero());
+    assert_eq!(cpu.timepulses(), 0);
+    assert_eq!(cpu.read_channel(W9::from(0o7)), W15::zero());
+    assert_eq!(cpu.read_channel(W9::from(0o30)), W15::zero());
+    // Erasable memory is kept
+    assert_eq!(
+        cpu.read_erasable(W3::zero(), W8::from(0o100)).value(),
+        W15::from(0o100)
+    );
+}
+
+#[test]
+fn standby() {
+    // Allow standby, then loop
+    let mut cpu = cpu_with_program(
+        &[
+            asm::ca(DATA),
+            asm::extend(),
+            asm::write(0o13),
+            asm::ca(DATA + 1),
+            asm::ts(counters::TIME1.address()),
+            asm::ca(DATA + 2),
+            asm::tcf(0o4006),
+        ],
+        &[0o2000, 0o37776, 0o123],
+    );
+
+    // PRO is ignored until standby is allowed
+    cpu.press_pro();
+    cpu.release_pro();
+    assert!(!cpu.is_standby());
+    run(&mut cpu, 12);
+    assert_eq!(cpu.a, W16::from(0o123));
+
+    cpu.step_control_pulse();
+    cpu.press_pro();
+    assert!(cpu.is_standby());
+    assert_eq!(cpu.read_channel(W9::from(0o32)), W15::from(0o57777));
+    cpu.release_pro();
+    assert_eq!(cpu.read_channel(W9::from(0o32)), W15::from(0o77777));
+
+    // Only TIME1 and TIME2 are counting
+    let start = cpu.timepulses();
+    while cpu.timepulses() < start + 3 * 10240 {
+        cpu.step_subinstruction();
+    }
+    assert!(cpu.is_standby());
+    assert_eq!(cpu.a, W16::from(0o123));
+    assert_eq!(
+        erasable(&cpu, counters::TIME1.address()),
+        W15::from(0o00001)
+    );
+    assert_eq!(
+        erasable(&cpu, counters::TIME2.address()),
+        W15::from(0o00001)
+    );
+    assert_eq!(erasable(&cpu, counters::TIME3.address()), W15::zero());
+
+    // PRO wakes the computer up with a restart
+    cpu.press_pro();
+    assert!(!cpu.is_standby());
+    assert_eq!(cpu.current_subsintruction_name(), "GOJ1");
+    assert_eq!(cpu.read_channel(W9::from(0o13)), W15::zero());
+}
+
+#[test]
+fn extended_index() {
+    // The indexed instruction is also extended: DCA instead of CA
+    let mut cpu = cpu_with_program(
+        &[
+            asm::extend(),
+            asm::index(DATA),
+            asm::dca(DATA + 1),
+            asm::tcf(0o4003),
+        ],
+        &[0o00002, 0o11111, 0o22222, 0o33333, 0o44444],
+    );
+    run(&mut cpu, 20);
+    assert_eq!(cpu.a, W16::from(0o033333));
+    assert_eq!(cpu.l, W16::from(0o144444));
+}
+
+#[test]
+fn extended_index_interrupt() {
+    // The interrupt is not taken before the indexed instruction
+    let mut cpu = cpu_with_interrupts(
+        &[
+            asm::extend(),
+            asm::index(DATA),
+            asm::dca(DATA + 1),
+            asm::tcf(0o4063),
+        ],
+        &[0o00002, 0o11111, 0o22222, 0o33333, 0o44444],
+    );
+    run(&mut cpu, 2);
+    cpu.request_interrupt(Interrupt::T5);
+    run(&mut cpu, 30);
+    assert_eq!(cpu.a, W16::from(0o033333));
+    assert_eq!(cpu.l, W16::from(0o144444));
+    assert_eq!(
+        cpu.read_erasable(W3::zero(), W8::from(0o101)).value(),
+        W15::from(0o1)
+    );
+}
+
+#[test]
+fn resume() {
+    let mut cpu = cpu_with_interrupts(
+        &[
+            asm::ca(DATA),
+            asm::ad(DATA + 1),
+            asm::ad(DATA + 2),
+            asm::ts(0o100),
+            asm::tcf(0o4064),
+        ],
+        &[0o00001, 0o00020, 0o00300],
+    );
+    run(&mut cpu, 4);
+    cpu.request_interrupt(Interrupt::T5);
+    run(&mut cpu, 20);
+    assert!(!cpu.is_interrupt_in_progress());
+
+    assert_eq!(erasable(&cpu, 0o100), W15::from(0o00321));
+    assert_eq!(erasable(&cpu, 0o101), W15::from(0o1));
+
+    // Another interrupt can be taken
+    cpu.request_interrupt(Interrupt::T5);
+    run(&mut cpu, 10);
+    assert_eq!(erasable(&cpu, 0o101), W15::from(0o2));
+    assert_eq!(cpu.z, W16::from(0o4065));
+}
+
+#[test]
+fn resume_indexed() {
+    // The instruction modified by INDEX is saved in BRUPT and executed by RESUME
+    let mut cpu = cpu_with_interrupts(
+        &[
+            asm::ca(DATA),
+            asm::ts(0o102),
+            asm::index(0o102),
+            asm::ca(DATA + 1),
+            asm::ts(0o100),
+            asm::tcf(0o4065),
+        ],
+        &[0o00002, 0o11111, 0o22222, 0o33333],
+    );
+    while cpu.current_subsintruction_name() != "NDX1" {
+        cpu.step_subinstruction();
+    }
+    cpu.request_interrupt(Interrupt::T5);
+
+    let mut names = Vec::new();
+    for _ in 0..12 {
+        names.push(cpu.current_subsintruction_name());
+        cpu.step_subinstruction();
+    }
+    assert_eq!(
+        names,
+        [
+            "NDX1", "RUPT0", "RUPT1", "RUPT3", "INCR0", "STD2", "NDX0", "RSM3", "CA0", "STD2",
+            "TS0", "STD2"
+        ]
+    );
+    assert_eq!(
+        cpu.read_erasable(W3::zero(), W8::from(0o100)).value(),
+        W15::from(0o33333)
+    );
+}
+
+#[test]
+fn interrupt_mid_sequence() {
+    let main = [
+        asm::ca(DATA),
+        asm::extend(),
+        asm::mp(DATA + 1),
+        asm::ts(0o100),
+        asm::lxch(0o101),
+        asm::ca(DATA + 2),
+        asm::ad(0o100),
+        asm::ts(0o102),
+        asm::tcf(0o4070),
+    ];
+    let data = [0o01234, 0o05432, 0o00765];
+    let state = |cpu: &Cpu| {
+        (
+            cpu.a,
+            cpu.l,
+            cpu.q,
+            erasable(cpu, 0o100),
+            erasable(cpu, 0o101),
+            erasable(cpu, 0o102),
+        )
+    };
+
+    let mut cpu = cpu_with_interrupts(&main, &data);
+    run(&mut cpu, 40);
+    let expected = state(&cpu);
+
+    // The interrupt is taken at every possible point of the sequence
+    for delay in 0..24 {
+        let mut cpu = cpu_with_interrupts(&main, &data);
+        run(&mut cpu, delay);
+        cpu.request_interrupt(Interrupt::T4);
+        run(&mut cpu, 60);
+        assert_eq!(state(&cpu), expected, "interrupt after {} MCTs", delay);
+        assert_eq!(erasable(&cpu, 0o104), W15::from(0o1));
+        assert!(!cpu.is_interrupt_in_progress());
+    }
+}
+
+#[test]
+fn edrupt() {
+    // The service routine address is read from A, since EDRUPT transfers
+    // control to address 0. K is the address of the next instruction, so
+    // that it is saved in BRUPT and executed by RESUME.
+    let mut words = interrupt_routines();
+    words.extend_from_slice(&[
+        (0o4060, asm::tcf(0o7074)),
+        (0o7074, asm::inhint()),
+        (0o7075, asm::ca(DATA)),
+        (0o7076, asm::extend()),
+        (0o7077, asm::edrupt(0o100)),
+        (0o7100, asm::ca(DATA + 1)),
+        (0o7101, asm::ts(0o100)),
+        (0o7102, asm::tcf(0o7102)),
+    ]);
+    let mut cpu = cpu_with_words(&words, &[asm::tcf(0o4040), 0o12345]);
+    while cpu.current_subsintruction_name() != "EDRUPT0" {
+        cpu.step_subinstruction();
+    }
+    cpu.request_interrupt(Interrupt::T3);
+
+    let mut names = Vec::new();
+    for _ in 0..4 {
+        names.push(cpu.current_subsintruction_name());
+        cpu.step_subinstruction();
+    }
+    assert_eq!(names, ["EDRUPT0", "EDRUPT1", "EDRUPT3", "STD2"]);
+    assert!(cpu.is_interrupt_in_progress());
+    assert_eq!(erasable(&cpu, ZRUPT), W15::from(0o7101));
+    assert_eq!(erasable(&cpu, BRUPT), W15::from(asm::ca(DATA + 1)));
+
+    run(&mut cpu, 40);
+    assert!(!cpu.is_interrupt_in_progress());
+    assert_eq!(erasable(&cpu, 0o104), W15::from(0o1));
+    assert_eq!(erasable(&cpu, 0o100), W15::from(0o12345));
+    // The pending interrupt stays inhibited
+    assert!(cpu.is_interrupt_pending(Interrupt::T3));
+}
+
+#[test]
+fn unimplemented_opcode() {
+    let mut cpu = cpu_with_program(&[asm::extend(), asm::mp(DATA)], &[0o00003]);
+    cpu.a = W16::from(0o00002);
+    while cpu.current_subsintruction_name() != "MP0" {
+        cpu.step_subinstruction();
+    }
+
+    // MP has no stage 5
+    cpu.st = W3::from(0b101);
+    let error = EmulationError::UnimplementedOpcode {
+        // Extended bit and the 6 upper bits of MP 4400
+        sq: W7::from(0o174),
+        st: W3::from(0b101),
+        z: W16::from(0o4002),
+    };
+    assert_eq!(cpu.try_current_subinstruction().err(), Some(error));
+    assert_eq!(cpu.try_step_control_pulse(), Err(error));
+    assert_eq!(cpu.try_step_subinstruction(), Err(error));
+
+    // The CPU is left untouched
+    assert_eq!(cpu.current_timepulse, TimePulse::T1);
+    assert_eq!(cpu.z, W16::from(0o4002));
+    assert_eq!(cpu.a, W16::from(0o00002));
+
+    cpu.st = W3::zero();
+    for _ in 0..3 {
+        assert_eq!(cpu.try_step_subinstruction(), Ok(()));
+    }
+    assert_eq!(cpu.l, W16::from(0o00006));
+}
+
+#[test]
+fn illegal_stage() {
+    let mut cpu = cpu_with_interrupts(&[asm::tcf(0o4060)], &[]);
+    cpu.request_interrupt(Interrupt::T3);
+    while cpu.current_subsintruction_name() != "RUPT1" {
+        cpu.step_subinstruction();
+    }
+
+    cpu.st = W3::from(0b111);
+    assert_eq!(
+        cpu.try_step_subinstruction(),
+        Err(EmulationError::IllegalStage {
+            st: W3::from(0b111),
+            z: cpu.z,
+        })
+    );
+}
+
+#[test]
+fn no_pending_interrupt() {
+    let mut cpu = cpu_with_interrupts(&[asm::tcf(0o4060)], &[]);
+    cpu.request_interrupt(Interrupt::T3);
+    while cpu.current_subsintruction_name() != "RUPT3" {
+        cpu.step_subinstruction();
+    }
+    assert!(!cpu.is_interrupt_pending(Interrupt::T3));
+
+    // KRPT reset the request, RRPA has nothing to vector to
+    cpu.st = W3::from(0b001);
+    assert_eq!(
+        cpu.try_step_subinstruction(),
+        Err(EmulationError::NoPendingInterrupt { z: cpu.z })
+    );
+}
+
+fn run_control_pulses(cpu: &mut Cpu, num_control_pulses: usize) {
+    for _ in 0..num_control_pulses {
+        cpu.step_control_pulse();
+    }
+}
+
+#[test]
+fn snapshot_restore() {
+    let main = [
+        asm::ca(DATA),
+        asm::extend(),
+        asm::mp(DATA + 1),
+        asm::ts(0o100),
+        asm::incr(0o102),
+        asm::tcf(0o4060),
+    ];
+    let data = [0o01234, 0o05432];
+    let mut cpu = cpu_with_interrupts(&main, &data);
+    cpu.request_counter(counters::TIME3, CounterInstruction::Pinc);
+    run_control_pulses(&mut cpu, 100);
+    cpu.request_interrupt(Interrupt::T4);
+
+    // In the middle of a subinstruction, with pending requests
+    run_control_pulses(&mut cpu, 5);
+    cpu.request_counter(counters::TIME1, CounterInstruction::Pinc);
+    cpu.request_interrupt(Interrupt::T5);
+    assert_ne!(cpu.current_timepulse, TimePulse::T1);
+    let path = std::env::temp_dir().join(format!("agc-snapshot-{}.bin", std::process::id()));
+    cpu.snapshot().save(&path).unwrap();
+
+    run_control_pulses(&mut cpu, 600);
+    let expected = cpu.snapshot().to_bytes();
+
+    let mut restored = cpu_with_interrupts(&main, &data);
+    let snapshot = CpuSnapshot::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    restored.restore(&snapshot).unwrap();
+    run_control_pulses(&mut restored, 600);
+    assert_eq!(restored.snapshot().to_bytes(), expected);
+    // Both interrupts were serviced
+    assert_eq!(erasable(&restored, 0o101), W15::from(0o1));
+    assert_eq!(erasable(&restored, 0o104), W15::from(0o1));
+}
+
+#[test]
+fn snapshot_other_fixed_storage() {
+    let cpu = cpu_with_program(&[asm::tcf(0o4000)], &[]);
+    let snapshot = cpu.snapshot();
+
+    let mut other = cpu_with_program(&[asm::tcf(0o4001)], &[]);
+    assert_eq!(
+        other.restore(&snapshot),
+        Err(SnapshotError::FixedStorageMismatch {
+            expected: snapshot.fixed_storage_hash(),
+            found: other.snapshot().fixed_storage_hash(),
+        })
+    );
+}